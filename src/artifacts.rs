@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::{Error, LoaderBuilder, Result};
+
+const LOADER_FILE_NAME: &str = "loader.bin";
+const SHARED_MEMORY_DIR: &str = "/dev/shm";
+
+/// Paths to the artifacts `forc build --release` produces for a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectArtifacts {
+    pub binary: PathBuf,
+    /// `None` if the project was built without emitting an ABI.
+    pub abi: Option<PathBuf>,
+}
+
+/// Locates `out/release/{name}.bin` and `out/release/{name}-abi.json` under `project_dir`.
+pub fn discover_artifacts(project_dir: impl AsRef<Path>, name: &str) -> Result<ProjectArtifacts> {
+    let release_dir = project_dir.as_ref().join("out").join("release");
+
+    let binary = release_dir.join(format!("{name}.bin"));
+    if !binary.is_file() {
+        return Err(Error::ArtifactNotFound(binary));
+    }
+
+    let abi = Some(release_dir.join(format!("{name}-abi.json"))).filter(|abi| abi.is_file());
+
+    Ok(ProjectArtifacts { binary, abi })
+}
+
+/// Builds the default loader for the release binary of the `forc` project at `project_dir`.
+pub fn loader_from_project(
+    project_dir: impl AsRef<Path>,
+    name: &str,
+    blob_id: &[u8; 32],
+) -> Result<Vec<u8>> {
+    let artifacts = discover_artifacts(project_dir, name)?;
+    let binary = std::fs::read(&artifacts.binary)?;
+
+    LoaderBuilder::default().build(&binary, blob_id)
+}
+
+/// Where [`write_loader`] puts the loader file, for APIs that want a program path rather than
+/// bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LoaderWriteOptions {
+    /// A fresh directory under the system temp location, removed with the [`LoaderFile`].
+    #[default]
+    TempDir,
+    /// A fresh directory inside the given one, for sandboxes that restrict the default temp
+    /// location. Also removed with the [`LoaderFile`].
+    TempDirIn(PathBuf),
+    /// A fresh directory under `/dev/shm`, so the loader never touches the disk. Falls back to
+    /// [`LoaderWriteOptions::TempDir`] on systems without `/dev/shm`, e.g. macOS and Windows.
+    InMemory,
+}
+
+/// A loader written to disk by [`write_loader`]. The file is deleted when this is dropped.
+#[derive(Debug)]
+pub struct LoaderFile {
+    path: PathBuf,
+    _dir: TempDir,
+}
+
+impl LoaderFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Writes `loader` to a `loader.bin` in the location `options` picks.
+pub fn write_loader(loader: &[u8], options: &LoaderWriteOptions) -> Result<LoaderFile> {
+    let dir = match options {
+        LoaderWriteOptions::TempDir => tempfile::tempdir()?,
+        LoaderWriteOptions::TempDirIn(parent) => tempfile::tempdir_in(parent)?,
+        LoaderWriteOptions::InMemory if Path::new(SHARED_MEMORY_DIR).is_dir() => {
+            tempfile::tempdir_in(SHARED_MEMORY_DIR)?
+        }
+        LoaderWriteOptions::InMemory => tempfile::tempdir()?,
+    };
+
+    let path = dir.path().join(LOADER_FILE_NAME);
+    std::fs::write(&path, loader)?;
+
+    Ok(LoaderFile { path, _dir: dir })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::fixture_binary;
+
+    #[test]
+    fn discovers_the_script_project_artifacts() {
+        let artifacts = discover_artifacts("script", "script").unwrap();
+
+        assert_eq!(
+            artifacts,
+            ProjectArtifacts {
+                binary: PathBuf::from("script/out/release/script.bin"),
+                abi: Some(PathBuf::from("script/out/release/script-abi.json")),
+            }
+        );
+    }
+
+    #[test]
+    fn loader_from_project_matches_building_from_the_binary() {
+        let blob_id = [1; 32];
+        let binary = fixture_binary();
+
+        let loader = loader_from_project("script", "script", &blob_id).unwrap();
+
+        assert_eq!(
+            loader,
+            LoaderBuilder::default().build(&binary, &blob_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_binary_is_reported() {
+        let err = loader_from_project("script", "does_not_exist", &[0; 32]).unwrap_err();
+
+        assert!(
+            matches!(err, Error::ArtifactNotFound(path) if path.ends_with("does_not_exist.bin"))
+        );
+    }
+
+    #[test]
+    fn writes_the_loader_into_the_given_directory() {
+        let parent = tempfile::tempdir().unwrap();
+
+        let file = write_loader(
+            &[1, 2, 3],
+            &LoaderWriteOptions::TempDirIn(parent.path().to_path_buf()),
+        )
+        .unwrap();
+
+        assert!(file.path().starts_with(parent.path()));
+        assert_eq!(std::fs::read(file.path()).unwrap(), [1, 2, 3]);
+
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+        assert!(parent.path().exists());
+    }
+
+    #[test]
+    fn writes_the_loader_in_memory_where_possible() {
+        let file = write_loader(&[1, 2, 3], &LoaderWriteOptions::InMemory).unwrap();
+
+        if Path::new(SHARED_MEMORY_DIR).is_dir() {
+            assert!(file.path().starts_with(SHARED_MEMORY_DIR));
+        }
+        assert_eq!(std::fs::read(file.path()).unwrap(), [1, 2, 3]);
+
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+    }
+}
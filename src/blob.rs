@@ -0,0 +1,148 @@
+use fuels::types::transaction_builders::Blob;
+
+use crate::{split_binary, Result};
+
+/// Computes the id a blob with the given content gets on chain.
+///
+/// Lets tests that don't run a node substitute a deterministic stand-in, see
+/// [`compute_blob_id_with`].
+pub trait BlobIdHasher {
+    fn blob_id(&self, code: &[u8]) -> [u8; 32];
+}
+
+/// Hashes blob content the way the VM does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VmBlobIdHasher;
+
+impl BlobIdHasher for VmBlobIdHasher {
+    fn blob_id(&self, code: &[u8]) -> [u8; 32] {
+        Blob::new(code.to_vec()).id()
+    }
+}
+
+/// The id of the blob holding `binary`'s code, computed offline.
+pub fn compute_blob_id(binary: &[u8]) -> Result<[u8; 32]> {
+    compute_blob_id_with(binary, &VmBlobIdHasher)
+}
+
+/// Like [`compute_blob_id`], hashing the code with `hasher`.
+pub fn compute_blob_id_with(binary: &[u8], hasher: &impl BlobIdHasher) -> Result<[u8; 32]> {
+    let (code, _) = split_binary(binary)?;
+
+    Ok(hasher.blob_id(code))
+}
+
+/// Whether binaries `a` and `b` have identical code, and so share one code blob, no matter how
+/// their data sections differ.
+pub fn same_blob(a: &[u8], b: &[u8]) -> Result<bool> {
+    Ok(split_binary(a)?.0 == split_binary(b)?.0)
+}
+
+/// Wraps a finished loader in a [`Blob`] so it can be stored on chain, content-addressed by its
+/// own bytes.
+///
+/// This is for storage only. Running a blobbed loader would take another loader in front of it,
+/// and the outer one carries nothing but the prologue and a blob id, so nesting saves nothing
+/// over loading the code blob directly. What makes loaders for the same code differ is their
+/// data section, and that is exactly what a nested loader would move into its blob.
+pub fn loader_blob(loader: &[u8]) -> Blob {
+    Blob::new(loader.to_vec())
+}
+
+/// A stable identifier for the exact loader, configurables included.
+///
+/// Unlike the blob id, which only covers the code, two loaders for the same code get different
+/// ids as soon as any byte of their data section differs. It is the id the loader would get as
+/// a blob, see [`loader_blob`].
+pub fn loader_id(loader: &[u8]) -> [u8; 32] {
+    loader_blob(loader).id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        find_blob_id,
+        test_fixtures::{fixture_abi, fixture_binary},
+        ConfigurableValue, LoaderBuilder,
+    };
+
+    #[test]
+    fn loader_blob_id_is_deterministic() {
+        let binary = fixture_binary();
+        let build = || LoaderBuilder::default().build(&binary, &[3; 32]).unwrap();
+
+        let first = loader_blob(&build());
+        let second = loader_blob(&build());
+
+        assert_eq!(first.id(), second.id());
+        assert_eq!(first.id(), Blob::new(build()).id());
+    }
+
+    #[test]
+    fn default_hasher_matches_the_blob_id() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+
+        assert_eq!(
+            compute_blob_id(&binary).unwrap(),
+            Blob::new(code.to_vec()).id()
+        );
+    }
+
+    #[test]
+    fn hasher_can_be_substituted() {
+        struct CodeLen;
+
+        impl BlobIdHasher for CodeLen {
+            fn blob_id(&self, code: &[u8]) -> [u8; 32] {
+                let mut id = [0; 32];
+                id[24..].copy_from_slice(&(code.len() as u64).to_be_bytes());
+                id
+            }
+        }
+
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+
+        let id = compute_blob_id_with(&binary, &CodeLen).unwrap();
+
+        assert_eq!(id[24..], (code.len() as u64).to_be_bytes());
+    }
+
+    #[test]
+    fn loader_id_covers_the_configurables() {
+        let binary = fixture_binary();
+        let abi = fixture_abi();
+        let blob_id = compute_blob_id(&binary).unwrap();
+        let build = |value| {
+            LoaderBuilder::default()
+                .with_abi(abi.clone())
+                .set_configurable("U64", ConfigurableValue::U64(value))
+                .build(&binary, &blob_id)
+                .unwrap()
+        };
+
+        let first = build(1);
+        let second = build(2);
+
+        assert_ne!(loader_id(&first), loader_id(&second));
+        assert_eq!(loader_id(&first), loader_id(&build(1)));
+        assert_eq!(find_blob_id(&first).unwrap(), blob_id);
+        assert_eq!(find_blob_id(&second).unwrap(), blob_id);
+    }
+
+    #[test]
+    fn binaries_differing_only_in_data_share_a_blob() {
+        let binary = fixture_binary();
+        let data_offset = crate::extract_data_offset(&binary).unwrap();
+
+        let mut other_data = binary.clone();
+        *other_data.last_mut().unwrap() ^= 0xFF;
+        assert!(same_blob(&binary, &other_data).unwrap());
+
+        let mut other_code = binary.clone();
+        other_code[data_offset - 1] ^= 0xFF;
+        assert!(!same_blob(&binary, &other_code).unwrap());
+    }
+}
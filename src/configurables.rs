@@ -0,0 +1,1268 @@
+use std::{collections::HashMap, fmt};
+
+use fuels::types::{Bits256, U256};
+use serde::Deserialize;
+
+use crate::{runtime_data_section, split_binary, Error, LoaderBuilder, Result};
+
+/// A value to write into a configurable's slot in the data section.
+///
+/// Values are encoded the way `encodingVersion = "1"` programs decode them: every value takes its
+/// natural width in big-endian order and compound values are the concatenation of their parts.
+/// There is no per-element padding, so a `[bool; 3]` takes three bytes just like a `[u8; 3]` does,
+/// and a `[b256; 2]` takes 64.
+///
+/// Structs are encoded like tuples of their fields, in declaration order. A single field can be
+/// overridden on its own by naming it with a dotted path, e.g. `CONFIG.threshold`.
+///
+/// Signed integers are encoded as two's complement at their natural width, so `I64(-1)` fills
+/// the whole word with `0xFF`. Sway has no signed integer types yet, so only ABIs that declare
+/// `i8` through `i64` accept them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurableValue {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U256(U256),
+    B256(Bits256),
+    StringArray(String),
+    Tuple(Vec<ConfigurableValue>),
+    Array(Vec<ConfigurableValue>),
+    /// The values of the struct's fields, in declaration order.
+    Struct(Vec<ConfigurableValue>),
+}
+
+impl ConfigurableValue {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Bool(value) => vec![u8::from(*value)],
+            Self::U8(value) => vec![*value],
+            Self::U16(value) => value.to_be_bytes().to_vec(),
+            Self::U32(value) => value.to_be_bytes().to_vec(),
+            Self::U64(value) => value.to_be_bytes().to_vec(),
+            Self::I8(value) => value.to_be_bytes().to_vec(),
+            Self::I16(value) => value.to_be_bytes().to_vec(),
+            Self::I32(value) => value.to_be_bytes().to_vec(),
+            Self::I64(value) => value.to_be_bytes().to_vec(),
+            Self::U256(value) => {
+                let mut bytes = [0; 32];
+                value.to_big_endian(&mut bytes);
+                bytes.to_vec()
+            }
+            Self::B256(value) => value.0.to_vec(),
+            Self::StringArray(value) => value.as_bytes().to_vec(),
+            Self::Tuple(values) | Self::Array(values) | Self::Struct(values) => {
+                values.iter().flat_map(ConfigurableValue::encode).collect()
+            }
+        }
+    }
+
+    /// Decodes a value of type `ty` from the start of `bytes`, returning it with the bytes after it.
+    fn decode<'a>(ty: &ConfigurableType, bytes: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (value, rest) = match ty {
+            ConfigurableType::Bool => {
+                let ([byte], rest) = take::<1>(bytes)?;
+                (Self::Bool(byte != 0), rest)
+            }
+            ConfigurableType::U8 => {
+                let ([byte], rest) = take::<1>(bytes)?;
+                (Self::U8(byte), rest)
+            }
+            ConfigurableType::U16 => {
+                let (word, rest) = take::<2>(bytes)?;
+                (Self::U16(u16::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::U32 => {
+                let (word, rest) = take::<4>(bytes)?;
+                (Self::U32(u32::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::U64 => {
+                let (word, rest) = take::<8>(bytes)?;
+                (Self::U64(u64::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::I8 => {
+                let (word, rest) = take::<1>(bytes)?;
+                (Self::I8(i8::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::I16 => {
+                let (word, rest) = take::<2>(bytes)?;
+                (Self::I16(i16::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::I32 => {
+                let (word, rest) = take::<4>(bytes)?;
+                (Self::I32(i32::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::I64 => {
+                let (word, rest) = take::<8>(bytes)?;
+                (Self::I64(i64::from_be_bytes(word)), rest)
+            }
+            ConfigurableType::U256 => {
+                let (word, rest) = take::<32>(bytes)?;
+                (Self::U256(U256::from_big_endian(&word)), rest)
+            }
+            ConfigurableType::B256 => {
+                let (word, rest) = take::<32>(bytes)?;
+                (Self::B256(Bits256(word)), rest)
+            }
+            ConfigurableType::StringArray(len) => {
+                let (string, rest) = (bytes.len() >= *len).then(|| bytes.split_at(*len))?;
+                (
+                    Self::StringArray(String::from_utf8_lossy(string).into_owned()),
+                    rest,
+                )
+            }
+            ConfigurableType::Tuple(types) => {
+                let (values, rest) = Self::decode_sequence(types.iter(), bytes)?;
+                (Self::Tuple(values), rest)
+            }
+            ConfigurableType::Array(element, len) => {
+                let elements = std::iter::repeat_n(element.as_ref(), *len);
+                let (values, rest) = Self::decode_sequence(elements, bytes)?;
+                (Self::Array(values), rest)
+            }
+            ConfigurableType::Struct { fields, .. } => {
+                let (values, rest) = Self::decode_sequence(fields.iter().map(|(_, ty)| ty), bytes)?;
+                (Self::Struct(values), rest)
+            }
+        };
+
+        Some((value, rest))
+    }
+
+    fn decode_sequence<'a, 't>(
+        types: impl Iterator<Item = &'t ConfigurableType>,
+        mut bytes: &'a [u8],
+    ) -> Option<(Vec<Self>, &'a [u8])> {
+        let mut values = vec![];
+        for ty in types {
+            let (value, rest) = Self::decode(ty, bytes)?;
+            values.push(value);
+            bytes = rest;
+        }
+
+        Some((values, bytes))
+    }
+
+    /// Checks that `self` can be written into a slot of type `ty`.
+    fn check(&self, name: &str, ty: &ConfigurableType) -> Result<()> {
+        let mismatch = || Error::ConfigurableTypeMismatch {
+            name: name.to_string(),
+            expected: ty.to_string(),
+        };
+
+        match (self, ty) {
+            (Self::Bool(_), ConfigurableType::Bool)
+            | (Self::U8(_), ConfigurableType::U8)
+            | (Self::U16(_), ConfigurableType::U16)
+            | (Self::U32(_), ConfigurableType::U32)
+            | (Self::U64(_), ConfigurableType::U64)
+            | (Self::I8(_), ConfigurableType::I8)
+            | (Self::I16(_), ConfigurableType::I16)
+            | (Self::I32(_), ConfigurableType::I32)
+            | (Self::I64(_), ConfigurableType::I64)
+            | (Self::U256(_), ConfigurableType::U256)
+            | (Self::B256(_), ConfigurableType::B256) => Ok(()),
+            (Self::StringArray(value), ConfigurableType::StringArray(len)) => {
+                if value.is_ascii() && value.len() == *len {
+                    Ok(())
+                } else {
+                    Err(mismatch())
+                }
+            }
+            (Self::Tuple(values), ConfigurableType::Tuple(types)) => {
+                if values.len() != types.len() {
+                    return Err(mismatch());
+                }
+                values
+                    .iter()
+                    .zip(types)
+                    .try_for_each(|(value, ty)| value.check(name, ty))
+            }
+            (Self::Struct(values), ConfigurableType::Struct { fields, .. }) => {
+                if values.len() != fields.len() {
+                    return Err(mismatch());
+                }
+                values
+                    .iter()
+                    .zip(fields)
+                    .try_for_each(|(value, (_, ty))| value.check(name, ty))
+            }
+            (Self::Array(values), ConfigurableType::Array(element, len)) => {
+                if values.len() != *len {
+                    return Err(Error::ArrayLengthMismatch {
+                        name: name.to_string(),
+                        expected: *len,
+                        found: values.len(),
+                    });
+                }
+                values
+                    .iter()
+                    .try_for_each(|value| value.check(name, element))
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+/// Splits the first `N` bytes off `bytes`, if it holds that many.
+fn take<const N: usize>(bytes: &[u8]) -> Option<([u8; N], &[u8])> {
+    let (head, rest) = (bytes.len() >= N).then(|| bytes.split_at(N))?;
+
+    Some((head.try_into().ok()?, rest))
+}
+
+/// The type of a configurable as declared in the program's ABI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurableType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    U256,
+    B256,
+    StringArray(usize),
+    Tuple(Vec<ConfigurableType>),
+    Array(Box<ConfigurableType>, usize),
+    /// A struct's name and its fields' names and types, in declaration order.
+    Struct {
+        name: String,
+        fields: Vec<(String, ConfigurableType)>,
+    },
+}
+
+impl ConfigurableType {
+    /// Number of bytes a value of this type occupies in the data section.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Bool | Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+            Self::U64 | Self::I64 => 8,
+            Self::U256 | Self::B256 => 32,
+            Self::StringArray(len) => *len,
+            Self::Tuple(types) => types.iter().map(Self::encoded_len).sum(),
+            Self::Array(element, len) => element.encoded_len() * len,
+            Self::Struct { fields, .. } => fields.iter().map(|(_, ty)| ty.encoded_len()).sum(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigurableType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::U8 => write!(f, "u8"),
+            Self::U16 => write!(f, "u16"),
+            Self::U32 => write!(f, "u32"),
+            Self::U64 => write!(f, "u64"),
+            Self::I8 => write!(f, "i8"),
+            Self::I16 => write!(f, "i16"),
+            Self::I32 => write!(f, "i32"),
+            Self::I64 => write!(f, "i64"),
+            Self::U256 => write!(f, "u256"),
+            Self::B256 => write!(f, "b256"),
+            Self::StringArray(len) => write!(f, "str[{len}]"),
+            Self::Tuple(types) => {
+                let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Self::Array(element, len) => write!(f, "[{element}; {len}]"),
+            Self::Struct { name, .. } => write!(f, "struct {name}"),
+        }
+    }
+}
+
+/// A configurable declared in the ABI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurableSlot {
+    pub name: String,
+    /// Offset of the slot from the start of the binary, as reported by the ABI.
+    pub offset: usize,
+    pub ty: ConfigurableType,
+}
+
+impl ConfigurableSlot {
+    /// The slot `path` names: either a configurable, or a field of a struct configurable given as
+    /// a dotted path such as `CONFIG.threshold`, which may itself be nested.
+    fn find(slots: &[Self], path: &str) -> Option<Self> {
+        let mut segments = path.split('.');
+        let root = segments.next()?;
+        let mut slot = slots.iter().find(|slot| slot.name == root)?.clone();
+
+        for segment in segments {
+            let ConfigurableType::Struct { fields, .. } = slot.ty else {
+                return None;
+            };
+            let index = fields.iter().position(|(name, _)| name == segment)?;
+            slot.offset += fields[..index]
+                .iter()
+                .map(|(_, ty)| ty.encoded_len())
+                .sum::<usize>();
+            slot.ty = fields.into_iter().nth(index)?.1;
+        }
+        slot.name = path.to_string();
+
+        Some(slot)
+    }
+}
+
+/// Lists the configurables declared in `abi_json`, in declaration order.
+pub fn configurable_offsets_from_abi(abi_json: &str) -> Result<Vec<ConfigurableSlot>> {
+    let abi: ProgramAbi = serde_json::from_str(abi_json)?;
+    let types = TypeResolver::new(&abi);
+
+    abi.configurables
+        .iter()
+        .map(|configurable| {
+            Ok(ConfigurableSlot {
+                name: configurable.name.clone(),
+                offset: configurable.offset,
+                ty: types.concrete(&configurable.concrete_type_id)?,
+            })
+        })
+        .collect()
+}
+
+/// Writes each override into `data_section`, which must start at `data_offset` in the original
+/// binary.
+pub fn apply_configurables(
+    data_section: &mut [u8],
+    data_offset: usize,
+    abi_json: &str,
+    overrides: &[(String, ConfigurableValue)],
+) -> Result<()> {
+    let slots = configurable_offsets_from_abi(abi_json)?;
+
+    for (name, value) in overrides {
+        let slot = ConfigurableSlot::find(&slots, name)
+            .ok_or_else(|| Error::UnknownConfigurable(name.clone()))?;
+
+        value.check(name, &slot.ty)?;
+        let encoded = value.encode();
+
+        let target = slot
+            .offset
+            .checked_sub(data_offset)
+            .and_then(|start| data_section.get_mut(start..start + encoded.len()))
+            .ok_or_else(|| Error::ConfigurableOutOfBounds {
+                name: name.clone(),
+                offset: slot.offset,
+            })?;
+        target.copy_from_slice(&encoded);
+    }
+
+    Ok(())
+}
+
+/// Reads the current value of every configurable in `abi_json` from `data_section`, which must
+/// start at `data_offset` in the original binary.
+///
+/// Values come back in ABI declaration order, so the output is the same on every call.
+pub fn read_configurables(
+    data_section: &[u8],
+    data_offset: usize,
+    abi_json: &str,
+) -> Result<Vec<(String, ConfigurableValue)>> {
+    configurable_offsets_from_abi(abi_json)?
+        .into_iter()
+        .map(|slot| {
+            let value = slot
+                .offset
+                .checked_sub(data_offset)
+                .and_then(|start| data_section.get(start..))
+                .and_then(|bytes| ConfigurableValue::decode(&slot.ty, bytes))
+                .map(|(value, _)| value)
+                .ok_or_else(|| Error::ConfigurableOutOfBounds {
+                    name: slot.name.clone(),
+                    offset: slot.offset,
+                })?;
+
+            Ok((slot.name, value))
+        })
+        .collect()
+}
+
+/// Reads the current value of the single configurable, or struct field, that `path` names, see
+/// [`ConfigurableValue`].
+pub fn read_configurable(
+    data_section: &[u8],
+    data_offset: usize,
+    abi_json: &str,
+    path: &str,
+) -> Result<ConfigurableValue> {
+    let slots = configurable_offsets_from_abi(abi_json)?;
+    let slot = ConfigurableSlot::find(&slots, path)
+        .ok_or_else(|| Error::UnknownConfigurable(path.to_string()))?;
+
+    slot.offset
+        .checked_sub(data_offset)
+        .and_then(|start| data_section.get(start..))
+        .and_then(|bytes| ConfigurableValue::decode(&slot.ty, bytes))
+        .map(|(value, _)| value)
+        .ok_or(Error::ConfigurableOutOfBounds {
+            name: slot.name,
+            offset: slot.offset,
+        })
+}
+
+/// The compiled-in value of every configurable in `abi_json`, decoded from `binary`'s own data
+/// section. Compare with [`read_configurables`] on a loader's data section to see what a build
+/// overrode.
+pub fn default_configurables(
+    binary: &[u8],
+    abi_json: &str,
+) -> Result<Vec<(String, ConfigurableValue)>> {
+    let (code, data_section) = split_binary(binary)?;
+
+    read_configurables(data_section, code.len(), abi_json)
+}
+
+/// A configurable's compiled-in value next to the one a loader runs with, see
+/// [`audit_configurables`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurableAudit {
+    pub name: String,
+    pub default: ConfigurableValue,
+    pub current: ConfigurableValue,
+}
+
+impl ConfigurableAudit {
+    /// Whether the loader runs with something other than the compiled-in value.
+    pub fn changed(&self) -> bool {
+        self.default != self.current
+    }
+}
+
+/// Compares every configurable's compiled-in value in `binary` with the value `loader` embeds,
+/// combining [`default_configurables`] and [`read_configurables`].
+///
+/// Entries come back in ABI declaration order, so reports built from them are reproducible.
+pub fn audit_configurables(
+    loader: &[u8],
+    binary: &[u8],
+    abi_json: &str,
+) -> Result<Vec<ConfigurableAudit>> {
+    let (code, _) = split_binary(binary)?;
+    let defaults = default_configurables(binary, abi_json)?;
+    let current = read_configurables(runtime_data_section(loader)?, code.len(), abi_json)?;
+
+    Ok(defaults
+        .into_iter()
+        .zip(current)
+        .map(|((name, default), (_, current))| ConfigurableAudit {
+            name,
+            default,
+            current,
+        })
+        .collect())
+}
+
+/// Checks that `loader` embeds `binary`'s data section with exactly `expected_overrides` applied
+/// and no other byte changed.
+///
+/// Errors with [`Error::OverrideMismatch`] if an overridden configurable holds a different value
+/// and with [`Error::DataSectionTampered`] if a byte outside every override differs. A loader
+/// built with [`crate::DataSectionStrategy::Blob`] embeds no data section to check, so it errors
+/// with [`Error::DataSectionInBlob`].
+pub fn verify_only_overrides_changed(
+    loader: &[u8],
+    binary: &[u8],
+    abi_json: &str,
+    expected_overrides: &[(String, ConfigurableValue)],
+) -> Result<()> {
+    let (code, original) = split_binary(binary)?;
+    let embedded = runtime_data_section(loader)?;
+
+    validate_overrides(original, code.len(), abi_json, expected_overrides)?;
+    let mut expected = original.to_vec();
+    apply_configurables(&mut expected, code.len(), abi_json, expected_overrides)?;
+
+    if embedded.len() != expected.len() {
+        return Err(Error::DataSectionTampered {
+            offset: code.len() + embedded.len().min(expected.len()),
+        });
+    }
+
+    let slots = configurable_offsets_from_abi(abi_json)?;
+    let overridden: Vec<_> = expected_overrides
+        .iter()
+        .filter_map(|(name, _)| ConfigurableSlot::find(&slots, name))
+        .collect();
+
+    let Some(position) = embedded.iter().zip(&expected).position(|(a, b)| a != b) else {
+        return Ok(());
+    };
+    let offset = code.len() + position;
+    match overridden
+        .iter()
+        .find(|slot| (slot.offset..slot.offset + slot.ty.encoded_len()).contains(&offset))
+    {
+        Some(slot) => Err(Error::OverrideMismatch(slot.name.clone())),
+        None => Err(Error::DataSectionTampered { offset }),
+    }
+}
+
+/// Checks every override against `data_section` without writing anything: the configurable must
+/// exist, the value must match its type, its slot must lie within the data section and no two
+/// overrides may write to the same bytes.
+///
+/// All problems are reported together, as [`Error::InvalidOverrides`] when there is more than one.
+pub fn validate_overrides(
+    data_section: &[u8],
+    data_offset: usize,
+    abi_json: &str,
+    overrides: &[(String, ConfigurableValue)],
+) -> Result<()> {
+    let slots = configurable_offsets_from_abi(abi_json)?;
+
+    let mut errors = vec![];
+    let mut written: Vec<(&str, std::ops::Range<usize>)> = vec![];
+    for (name, value) in overrides {
+        let Some(slot) = ConfigurableSlot::find(&slots, name) else {
+            errors.push(Error::UnknownConfigurable(name.clone()));
+            continue;
+        };
+
+        if let Err(err) = value.check(name, &slot.ty) {
+            errors.push(err);
+            continue;
+        }
+
+        let range = slot.offset..slot.offset + slot.ty.encoded_len();
+        let in_bounds = range.start >= data_offset && range.end - data_offset <= data_section.len();
+        if !in_bounds {
+            errors.push(Error::ConfigurableOutOfBounds {
+                name: name.clone(),
+                offset: slot.offset,
+            });
+            continue;
+        }
+
+        if let Some((first, _)) = written
+            .iter()
+            .find(|(_, other)| other.start < range.end && range.start < other.end)
+        {
+            errors.push(Error::OverlappingOverrides {
+                first: first.to_string(),
+                second: name.clone(),
+            });
+        }
+        written.push((name.as_str(), range));
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(Error::InvalidOverrides(errors)),
+    }
+}
+
+/// Errors with [`Error::UnsetConfigurables`] listing every configurable in `abi_json` that
+/// `builder` leaves at its compiled-in default. A struct counts as set once each of its fields
+/// is, whether as a whole or through dotted paths.
+pub fn assert_all_configurables_set(builder: &LoaderBuilder, abi_json: &str) -> Result<()> {
+    let overridden = builder.configurable_overrides();
+
+    let unset: Vec<_> = configurable_offsets_from_abi(abi_json)?
+        .into_iter()
+        .filter(|slot| !is_overridden(&slot.name, &slot.ty, overridden))
+        .map(|slot| slot.name)
+        .collect();
+
+    if unset.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::UnsetConfigurables(unset))
+    }
+}
+
+/// Whether `overrides` cover the whole slot at `path`, either directly or field by field.
+fn is_overridden(
+    path: &str,
+    ty: &ConfigurableType,
+    overrides: &[(String, ConfigurableValue)],
+) -> bool {
+    if overrides.iter().any(|(name, _)| name == path) {
+        return true;
+    }
+
+    match ty {
+        ConfigurableType::Struct { fields, .. } => fields
+            .iter()
+            .all(|(field, ty)| is_overridden(&format!("{path}.{field}"), ty, overrides)),
+        _ => false,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramAbi {
+    concrete_types: Vec<ConcreteType>,
+    #[serde(default)]
+    metadata_types: Vec<MetadataType>,
+    #[serde(default)]
+    configurables: Vec<AbiConfigurable>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConcreteType {
+    #[serde(rename = "type")]
+    type_field: String,
+    concrete_type_id: String,
+    metadata_type_id: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataType {
+    #[serde(rename = "type")]
+    type_field: String,
+    metadata_type_id: usize,
+    #[serde(default)]
+    components: Vec<Component>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Component {
+    #[serde(default)]
+    name: String,
+    type_id: TypeRef,
+}
+
+/// Components refer to concrete types by their hash and to metadata types by their index.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TypeRef {
+    Concrete(String),
+    Metadata(usize),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AbiConfigurable {
+    name: String,
+    concrete_type_id: String,
+    offset: usize,
+}
+
+struct TypeResolver<'a> {
+    concrete: HashMap<&'a str, &'a ConcreteType>,
+    metadata: HashMap<usize, &'a MetadataType>,
+}
+
+impl<'a> TypeResolver<'a> {
+    fn new(abi: &'a ProgramAbi) -> Self {
+        Self {
+            concrete: abi
+                .concrete_types
+                .iter()
+                .map(|ty| (ty.concrete_type_id.as_str(), ty))
+                .collect(),
+            metadata: abi
+                .metadata_types
+                .iter()
+                .map(|ty| (ty.metadata_type_id, ty))
+                .collect(),
+        }
+    }
+
+    fn concrete(&self, id: &str) -> Result<ConfigurableType> {
+        let ty = self
+            .concrete
+            .get(id)
+            .ok_or_else(|| Error::UnknownAbiType(id.to_string()))?;
+
+        match ty.metadata_type_id {
+            Some(metadata_id) => self.metadata(metadata_id),
+            None => self.parse(&ty.type_field, &[]),
+        }
+    }
+
+    fn metadata(&self, id: usize) -> Result<ConfigurableType> {
+        let ty = self
+            .metadata
+            .get(&id)
+            .ok_or_else(|| Error::UnknownAbiType(id.to_string()))?;
+
+        self.parse(&ty.type_field, &ty.components)
+    }
+
+    fn resolve(&self, type_ref: &TypeRef) -> Result<ConfigurableType> {
+        match type_ref {
+            TypeRef::Concrete(id) => self.concrete(id),
+            TypeRef::Metadata(id) => self.metadata(*id),
+        }
+    }
+
+    fn parse(&self, type_field: &str, components: &[Component]) -> Result<ConfigurableType> {
+        let unsupported = || Error::UnsupportedAbiType(type_field.to_string());
+
+        let ty = match type_field {
+            "bool" => ConfigurableType::Bool,
+            "u8" => ConfigurableType::U8,
+            "u16" => ConfigurableType::U16,
+            "u32" => ConfigurableType::U32,
+            "u64" => ConfigurableType::U64,
+            "i8" => ConfigurableType::I8,
+            "i16" => ConfigurableType::I16,
+            "i32" => ConfigurableType::I32,
+            "i64" => ConfigurableType::I64,
+            "u256" => ConfigurableType::U256,
+            "b256" => ConfigurableType::B256,
+            _ if type_field.starts_with("str[") => {
+                let len = type_field
+                    .strip_prefix("str[")
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .and_then(|len| len.parse().ok())
+                    .ok_or_else(unsupported)?;
+                ConfigurableType::StringArray(len)
+            }
+            _ if type_field.starts_with('(') => ConfigurableType::Tuple(
+                components
+                    .iter()
+                    .map(|component| self.resolve(&component.type_id))
+                    .collect::<Result<_>>()?,
+            ),
+            _ if type_field.starts_with("struct ") => ConfigurableType::Struct {
+                name: type_field["struct ".len()..].to_string(),
+                fields: components
+                    .iter()
+                    .map(|component| {
+                        Ok((component.name.clone(), self.resolve(&component.type_id)?))
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            _ if type_field.starts_with('[') => {
+                let len = type_field
+                    .rsplit_once(';')
+                    .and_then(|(_, len)| len.trim().strip_suffix(']'))
+                    .and_then(|len| len.trim().parse().ok())
+                    .ok_or_else(unsupported)?;
+                let [element] = components else {
+                    return Err(unsupported());
+                };
+                ConfigurableType::Array(Box::new(self.resolve(&element.type_id)?), len)
+            }
+            _ => return Err(unsupported()),
+        };
+
+        Ok(ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_fixtures::{binary_with_data_section, fixture_abi, fixture_binary},
+        LoaderBuilder,
+    };
+
+    const ARRAY_ABI: &str = r#"{
+        "programType": "script",
+        "specVersion": "1",
+        "encodingVersion": "1",
+        "concreteTypes": [
+            {
+                "type": "[u64; 3]",
+                "concreteTypeId": "array",
+                "metadataTypeId": 0
+            },
+            {
+                "type": "u64",
+                "concreteTypeId": "u64"
+            }
+        ],
+        "metadataTypes": [
+            {
+                "type": "[_; 3]",
+                "metadataTypeId": 0,
+                "components": [{ "name": "__array_element", "typeId": "u64" }]
+            }
+        ],
+        "functions": [],
+        "configurables": [
+            { "name": "ARRAY", "concreteTypeId": "array", "offset": 16 }
+        ]
+    }"#;
+
+    // No Sway type maps to `i64` yet, so this ABI is handwritten and the value is only read back
+    // offline rather than through a running script.
+    const SIGNED_ABI: &str = r#"{
+        "programType": "script",
+        "specVersion": "1",
+        "encodingVersion": "1",
+        "concreteTypes": [{ "type": "i64", "concreteTypeId": "i64" }],
+        "functions": [],
+        "configurables": [
+            { "name": "SIGNED", "concreteTypeId": "i64", "offset": 16 }
+        ]
+    }"#;
+
+    const STRUCT_ABI: &str = r#"{
+        "programType": "script",
+        "specVersion": "1",
+        "encodingVersion": "1",
+        "concreteTypes": [
+            {
+                "type": "struct Config",
+                "concreteTypeId": "config",
+                "metadataTypeId": 0
+            },
+            { "type": "u64", "concreteTypeId": "u64" },
+            { "type": "bool", "concreteTypeId": "bool" }
+        ],
+        "metadataTypes": [
+            {
+                "type": "struct Config",
+                "metadataTypeId": 0,
+                "components": [
+                    { "name": "threshold", "typeId": "u64" },
+                    { "name": "enabled", "typeId": "bool" }
+                ]
+            }
+        ],
+        "functions": [],
+        "configurables": [
+            { "name": "CONFIG", "concreteTypeId": "config", "offset": 16 }
+        ]
+    }"#;
+
+    fn loader_data_section(loader: &[u8], len: usize) -> &[u8] {
+        &loader[loader.len() - len..]
+    }
+
+    #[test]
+    fn resolves_the_fixture_configurables() {
+        let abi = fixture_abi();
+
+        let slots = configurable_offsets_from_abi(&abi).unwrap();
+
+        let types: Vec<_> = slots
+            .iter()
+            .map(|slot| (slot.name.as_str(), slot.ty.to_string()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("BOOL", "bool"),
+                ("U8", "u8"),
+                ("U16", "u16"),
+                ("U32", "u32"),
+                ("U64", "u64"),
+                ("U256", "u256"),
+                ("B256", "b256"),
+                ("STR_4", "str[4]"),
+                ("TUPLE", "(u8, bool)"),
+            ]
+            .map(|(name, ty)| (name, ty.to_string()))
+        );
+    }
+
+    #[test]
+    fn overrides_a_fixture_configurable() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let data_offset = crate::extract_data_offset(&binary).unwrap();
+        let data_len = binary.len() - data_offset;
+
+        let loader = LoaderBuilder::default()
+            .with_abi(abi.clone())
+            .set_configurable("U64", ConfigurableValue::U64(8))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let u64_offset = configurable_offsets_from_abi(&abi)
+            .unwrap()
+            .into_iter()
+            .find(|slot| slot.name == "U64")
+            .unwrap()
+            .offset;
+
+        let mut expected = binary[data_offset..].to_vec();
+        expected[u64_offset - data_offset..][..8].copy_from_slice(&8u64.to_be_bytes());
+        assert_eq!(loader_data_section(&loader, data_len), expected);
+    }
+
+    #[test]
+    fn sets_an_array_configurable() {
+        let binary = binary_with_data_section(&[0; 24]);
+        let array = ConfigurableValue::Array(vec![
+            ConfigurableValue::U64(1),
+            ConfigurableValue::U64(2),
+            ConfigurableValue::U64(3),
+        ]);
+
+        let loader = LoaderBuilder::default()
+            .with_abi(ARRAY_ABI)
+            .set_configurable("ARRAY", array)
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let elements: Vec<_> = loader_data_section(&loader, 24)
+            .chunks(8)
+            .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(elements, [1, 2, 3]);
+    }
+
+    #[test]
+    fn array_length_must_match_the_abi() {
+        let binary = binary_with_data_section(&[0; 24]);
+        let array = ConfigurableValue::Array(vec![ConfigurableValue::U64(1); 2]);
+
+        let err = LoaderBuilder::default()
+            .with_abi(ARRAY_ABI)
+            .set_configurable("ARRAY", array)
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::ArrayLengthMismatch {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn array_elements_are_not_padded() {
+        let bools = ConfigurableValue::Array(vec![ConfigurableValue::Bool(true); 3]);
+        let b256s = ConfigurableValue::Array(vec![ConfigurableValue::B256(Bits256([7; 32])); 2]);
+
+        assert_eq!(bools.encode(), [1, 1, 1]);
+        assert_eq!(b256s.encode(), [7; 64]);
+    }
+
+    #[test]
+    fn rejects_overlapping_overrides() {
+        let binary = binary_with_data_section(&[0; 24]);
+        let array = || ConfigurableValue::Array(vec![ConfigurableValue::U64(1); 3]);
+
+        let err = LoaderBuilder::default()
+            .with_abi(ARRAY_ABI)
+            .set_configurable("ARRAY", array())
+            .set_configurable("ARRAY", array())
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::OverlappingOverrides { first, second } if first == "ARRAY" && second == "ARRAY"
+        ));
+    }
+
+    #[test]
+    fn reports_every_invalid_override_at_once() {
+        let binary = binary_with_data_section(&[0; 16]);
+        let overrides = [
+            ("MISSING".to_string(), ConfigurableValue::U8(0)),
+            (
+                "ARRAY".to_string(),
+                ConfigurableValue::Array(vec![ConfigurableValue::U64(1); 3]),
+            ),
+        ];
+
+        let err = validate_overrides(&binary[16..], 16, ARRAY_ABI, &overrides).unwrap_err();
+
+        let Error::InvalidOverrides(errors) = err else {
+            panic!("expected every problem to be reported, got {err:?}");
+        };
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                Error::UnknownConfigurable(_),
+                Error::ConfigurableOutOfBounds { offset: 16, .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn reads_configurables_in_declaration_order() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let (code, data_section) = crate::split_binary(&binary).unwrap();
+
+        let first = read_configurables(data_section, code.len(), &abi).unwrap();
+        let second = read_configurables(data_section, code.len(), &abi).unwrap();
+
+        assert_eq!(first, second);
+        let names: Vec<_> = first.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["BOOL", "U8", "U16", "U32", "U64", "U256", "B256", "STR_4", "TUPLE"]
+        );
+        assert_eq!(first[4].1, ConfigurableValue::U64(63));
+        assert_eq!(
+            first[8].1,
+            ConfigurableValue::Tuple(vec![
+                ConfigurableValue::U8(8),
+                ConfigurableValue::Bool(true)
+            ])
+        );
+    }
+
+    #[test]
+    fn signed_values_are_sign_extended() {
+        assert_eq!(ConfigurableValue::I64(-1).encode(), [0xFF; 8]);
+        assert_eq!(ConfigurableValue::I8(-2).encode(), [0xFE]);
+        assert_eq!(
+            ConfigurableValue::I32(-256).encode(),
+            [0xFF, 0xFF, 0xFF, 0x00]
+        );
+
+        let binary = binary_with_data_section(&[0; 8]);
+        let loader = LoaderBuilder::default()
+            .with_abi(SIGNED_ABI)
+            .set_configurable("SIGNED", ConfigurableValue::I64(-1))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let data_section = crate::runtime_data_section(&loader).unwrap();
+        assert_eq!(data_section, [0xFF; 8]);
+        assert_eq!(
+            read_configurables(data_section, 16, SIGNED_ABI).unwrap(),
+            [("SIGNED".to_string(), ConfigurableValue::I64(-1))]
+        );
+    }
+
+    // The blob holds only the code. If the data section ever leaked into it, every configurable
+    // value would need its own blob and the loaders below would point at different ids.
+    #[test]
+    fn configurables_do_not_change_the_blob_id() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let (code, data_section) = crate::split_binary(&binary).unwrap();
+
+        let patched_binary = |value| {
+            let mut data_section = data_section.to_vec();
+            let overrides = [("U64".to_string(), ConfigurableValue::U64(value))];
+            apply_configurables(&mut data_section, code.len(), &abi, &overrides).unwrap();
+            [code, &data_section].concat()
+        };
+        let (first, second) = (patched_binary(1), patched_binary(2));
+        assert_ne!(first, second);
+
+        let blob_id = crate::compute_blob_id(&first).unwrap();
+        assert_eq!(crate::compute_blob_id(&second).unwrap(), blob_id);
+
+        let loader_with = |value| {
+            LoaderBuilder::default()
+                .with_abi(abi.clone())
+                .set_configurable("U64", ConfigurableValue::U64(value))
+                .build(&binary, &blob_id)
+                .unwrap()
+        };
+        let (first, second) = (loader_with(1), loader_with(2));
+        assert_ne!(first, second);
+        assert_eq!(crate::find_blob_id(&first).unwrap(), blob_id);
+        assert_eq!(crate::find_blob_id(&second).unwrap(), blob_id);
+    }
+
+    #[test]
+    fn decodes_the_fixture_defaults() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+
+        let defaults = default_configurables(&binary, &abi).unwrap();
+
+        assert_eq!(
+            defaults,
+            [
+                ("BOOL", ConfigurableValue::Bool(true)),
+                ("U8", ConfigurableValue::U8(8)),
+                ("U16", ConfigurableValue::U16(16)),
+                ("U32", ConfigurableValue::U32(32)),
+                ("U64", ConfigurableValue::U64(63)),
+                ("U256", ConfigurableValue::U256(U256::from(8))),
+                ("B256", ConfigurableValue::B256(Bits256([1; 32]))),
+                ("STR_4", ConfigurableValue::StringArray("fuel".to_string())),
+                (
+                    "TUPLE",
+                    ConfigurableValue::Tuple(vec![
+                        ConfigurableValue::U8(8),
+                        ConfigurableValue::Bool(true)
+                    ])
+                ),
+            ]
+            .map(|(name, value)| (name.to_string(), value))
+        );
+    }
+
+    #[test]
+    fn verifies_that_only_overrides_changed() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let overrides = [("U64".to_string(), ConfigurableValue::U64(8))];
+        let loader = LoaderBuilder::default()
+            .with_abi(abi.clone())
+            .set_configurable("U64", ConfigurableValue::U64(8))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        verify_only_overrides_changed(&loader, &binary, &abi, &overrides).unwrap();
+
+        let err = verify_only_overrides_changed(
+            &loader,
+            &binary,
+            &abi,
+            &[("U64".to_string(), ConfigurableValue::U64(9))],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::OverrideMismatch(name) if name == "U64"));
+
+        // Flip the first byte of the data section, which holds B256 and wasn't overridden.
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let mut tampered = loader.clone();
+        let data_start = loader.len() - data_section.len();
+        tampered[data_start] ^= 0xFF;
+        let err = verify_only_overrides_changed(&tampered, &binary, &abi, &overrides).unwrap_err();
+        assert!(matches!(err, Error::DataSectionTampered { offset } if offset == code.len()));
+    }
+
+    #[test]
+    fn audits_configurables_in_declaration_order() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default()
+            .with_abi(abi.clone())
+            .set_configurable("U64", ConfigurableValue::U64(8))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let audit = audit_configurables(&loader, &binary, &abi).unwrap();
+
+        assert_eq!(audit, audit_configurables(&loader, &binary, &abi).unwrap());
+        let names: Vec<_> = audit.iter().map(|entry| entry.name.clone()).collect();
+        let declared: Vec<_> = configurable_offsets_from_abi(&abi)
+            .unwrap()
+            .into_iter()
+            .map(|slot| slot.name)
+            .collect();
+        assert_eq!(names, declared);
+        let changed: Vec<_> = audit.iter().filter(|entry| entry.changed()).collect();
+        assert_eq!(
+            changed,
+            [&ConfigurableAudit {
+                name: "U64".to_string(),
+                default: ConfigurableValue::U64(63),
+                current: ConfigurableValue::U64(8),
+            }]
+        );
+    }
+
+    #[test]
+    fn cannot_verify_overrides_of_a_data_blob_loader() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default()
+            .with_data_section_strategy(crate::DataSectionStrategy::Blob([2; 32]))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let err = verify_only_overrides_changed(&loader, &binary, &abi, &[]).unwrap_err();
+
+        assert!(matches!(err, Error::DataSectionInBlob(id) if id == [2; 32]));
+    }
+
+    #[test]
+    fn reports_unset_configurables() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let values = [
+            ("BOOL", ConfigurableValue::Bool(false)),
+            ("U8", ConfigurableValue::U8(0)),
+            ("U16", ConfigurableValue::U16(0)),
+            ("U32", ConfigurableValue::U32(0)),
+            ("U64", ConfigurableValue::U64(0)),
+            ("U256", ConfigurableValue::U256(U256::from(0))),
+            ("B256", ConfigurableValue::B256(Bits256([0; 32]))),
+            ("STR_4", ConfigurableValue::StringArray("rust".to_string())),
+        ];
+
+        let builder = values.into_iter().fold(
+            LoaderBuilder::default().with_abi(abi.clone()),
+            |builder, (name, value)| builder.set_configurable(name, value),
+        );
+
+        let err = assert_all_configurables_set(&builder, &abi).unwrap_err();
+        assert!(matches!(err, Error::UnsetConfigurables(unset) if unset == ["TUPLE"]));
+
+        let builder = builder.set_configurable(
+            "TUPLE",
+            ConfigurableValue::Tuple(vec![
+                ConfigurableValue::U8(1),
+                ConfigurableValue::Bool(false),
+            ]),
+        );
+        assert_all_configurables_set(&builder, &abi).unwrap();
+        // Every override matches its configurable's declared type.
+        builder.build(&binary, &[0; 32]).unwrap();
+    }
+
+    #[test]
+    fn struct_fields_set_one_by_one_count_as_set() {
+        let builder = LoaderBuilder::default()
+            .with_abi(STRUCT_ABI)
+            .set_configurable("CONFIG.threshold", ConfigurableValue::U64(8));
+
+        let err = assert_all_configurables_set(&builder, STRUCT_ABI).unwrap_err();
+        assert!(matches!(err, Error::UnsetConfigurables(unset) if unset == ["CONFIG"]));
+
+        let builder = builder.set_configurable("CONFIG.enabled", ConfigurableValue::Bool(false));
+        assert_all_configurables_set(&builder, STRUCT_ABI).unwrap();
+    }
+
+    #[test]
+    fn sets_a_single_struct_field() {
+        let binary =
+            binary_with_data_section(&[[0, 0, 0, 0, 0, 0, 0, 5].as_slice(), &[1]].concat());
+
+        let loader = LoaderBuilder::default()
+            .with_abi(STRUCT_ABI)
+            .set_configurable("CONFIG.threshold", ConfigurableValue::U64(8))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let data_section = loader_data_section(&loader, 9);
+        assert_eq!(
+            read_configurable(data_section, 16, STRUCT_ABI, "CONFIG").unwrap(),
+            ConfigurableValue::Struct(vec![
+                ConfigurableValue::U64(8),
+                ConfigurableValue::Bool(true)
+            ])
+        );
+        assert_eq!(
+            read_configurable(data_section, 16, STRUCT_ABI, "CONFIG.enabled").unwrap(),
+            ConfigurableValue::Bool(true)
+        );
+    }
+
+    #[test]
+    fn struct_fields_must_exist() {
+        let binary = binary_with_data_section(&[0; 9]);
+
+        let err = LoaderBuilder::default()
+            .with_abi(STRUCT_ABI)
+            .set_configurable("CONFIG.missing", ConfigurableValue::U64(8))
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::UnknownConfigurable(name) if name == "CONFIG.missing"));
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+
+use fuels::{
+    accounts::{provider::Provider, Account, ViewOnlyAccount},
+    crypto::Hasher,
+    programs::contract::Contract,
+    tx::Salt,
+    types::{
+        coin::Coin,
+        coin_type::CoinType,
+        input::Input,
+        output::Output,
+        transaction::{TransactionType, TxPolicies},
+        transaction_builders::{Blob, BlobTransactionBuilder, TransactionBuilder},
+        ContractId, TxId,
+    },
+};
+
+use crate::{
+    compute_blob_id, find_blob_id, parse_loader, reconstruct_binary, split_binary, Error,
+    LoaderBuilder, LoaderTarget, Result,
+};
+
+/// Whether a blob with `blob_id` is on chain, no matter who uploaded it.
+pub async fn blob_exists(provider: &Provider, blob_id: &[u8; 32]) -> Result<bool> {
+    Ok(provider.blob_exists((*blob_id).into()).await?)
+}
+
+/// Whether `binary` is too large to run directly as a script on `provider`'s chain, i.e. exceeds
+/// its `max_script_length`, so it can only run through a loader.
+pub async fn needs_loader(provider: &Provider, binary: &[u8]) -> Result<bool> {
+    Ok(binary.len() as u64 > max_script_length(provider))
+}
+
+/// Checks that `loader` fits under `provider`'s chain's `max_script_length`, erroring with
+/// [`Error::LoaderTooLarge`] if it doesn't.
+///
+/// Everything embedded in the loader counts, so a loader for a program with a huge data section
+/// can be too large even though its code isn't. Such programs need
+/// [`crate::DataSectionStrategy::Blob`].
+pub async fn validate_loader_fits(provider: &Provider, loader: &[u8]) -> Result<()> {
+    let max = max_script_length(provider);
+    let len = loader.len() as u64;
+    if len > max {
+        return Err(Error::LoaderTooLarge {
+            len,
+            max,
+            overage: len - max,
+        });
+    }
+
+    Ok(())
+}
+
+fn max_script_length(provider: &Provider) -> u64 {
+    provider
+        .consensus_parameters()
+        .script_params()
+        .max_script_length()
+}
+
+/// Checks that `loader`, with the blobs it references as stored on `provider`'s chain, runs exactly
+/// `expected_binary`. A loader built with [`DataSectionStrategy::Blob`](crate::DataSectionStrategy)
+/// references its data section's blob next to the code blob, and both are fetched.
+///
+/// Errors with [`Error::BlobNotFound`] if a blob was never uploaded and with
+/// [`Error::BinaryMismatch`] if the blobs or the embedded data section differ from the binary.
+pub async fn verify_loader_on_chain(
+    provider: &Provider,
+    loader: &[u8],
+    expected_binary: &[u8],
+) -> Result<()> {
+    let code = fetch_blob(provider, find_blob_id(loader)?).await?;
+    let binary = match parse_loader(loader)?.data_blob_id {
+        Some(data_blob_id) => [code, fetch_blob(provider, data_blob_id).await?].concat(),
+        None => reconstruct_binary(&code, loader)?,
+    };
+
+    if binary != expected_binary {
+        return Err(Error::BinaryMismatch);
+    }
+
+    Ok(())
+}
+
+async fn fetch_blob(provider: &Provider, blob_id: [u8; 32]) -> Result<Vec<u8>> {
+    let blob = provider
+        .blob(blob_id.into())
+        .await?
+        .ok_or(Error::BlobNotFound(blob_id))?;
+
+    Ok(blob.into())
+}
+
+/// Fetches the script transaction `tx_id` and returns its bytecode, i.e. the loader it ran.
+pub async fn loader_from_tx(provider: &Provider, tx_id: &TxId) -> Result<Vec<u8>> {
+    let response = provider
+        .get_transaction_by_id(tx_id)
+        .await?
+        .ok_or(Error::TransactionNotFound(*tx_id))?;
+
+    match response.transaction {
+        TransactionType::Script(tx) => Ok(tx.script().clone()),
+        _ => Err(Error::NotAScriptTransaction(*tx_id)),
+    }
+}
+
+/// Builds a loader for `binary` with `builder`, first uploading the code blob through `account`
+/// unless it's already on chain.
+///
+/// Blobs are content addressed, so a blob uploaded by any account is as good as our own and
+/// reusing it saves the upload fee.
+pub async fn deploy_loader(
+    account: &impl Account,
+    builder: &LoaderBuilder,
+    binary: &[u8],
+) -> Result<Vec<u8>> {
+    let provider = account.try_provider()?;
+    let (code, _) = split_binary(binary)?;
+    let blob = Blob::new(code.to_vec());
+    let blob_id = blob.id();
+
+    if !blob_exists(provider, &blob_id).await? {
+        upload_blob(account, provider, blob).await?;
+    }
+
+    builder.build(binary, &blob_id)
+}
+
+/// The salt [`deploy_contract_loader`] deploys with: the hash of `blob_id` followed by `seed`, so
+/// the same code and seed always give the same loader contract id.
+pub fn loader_contract_salt(blob_id: &[u8; 32], seed: &[u8]) -> Salt {
+    let hash = Hasher::default().chain(blob_id).chain(seed).finalize();
+
+    Salt::new(*hash)
+}
+
+/// The id a contract running `loader` gets when deployed with `salt` and no storage slots,
+/// computed offline.
+pub fn loader_contract_id(loader: &[u8], salt: Salt) -> ContractId {
+    Contract::regular(loader.to_vec(), salt, vec![]).contract_id()
+}
+
+/// Like [`deploy_loader`], then deploys the loader as a contract salted with
+/// [`loader_contract_salt`] of the code blob's id and `seed`. `builder`'s target is overridden
+/// with [`LoaderTarget::Contract`].
+///
+/// The returned id is what [`loader_contract_id`] predicts for the same loader and salt.
+pub async fn deploy_contract_loader(
+    account: &impl Account,
+    builder: &LoaderBuilder,
+    binary: &[u8],
+    seed: &[u8],
+) -> Result<ContractId> {
+    let builder = builder.clone().with_target(LoaderTarget::Contract);
+    let loader = deploy_loader(account, &builder, binary).await?;
+    let salt = loader_contract_salt(&find_blob_id(&loader)?, seed);
+
+    let contract_id = Contract::regular(loader, salt, vec![])
+        .deploy(account, TxPolicies::default())
+        .await?;
+
+    Ok((&contract_id).into())
+}
+
+/// Rebuilds the loader [`deploy_loader`] would have produced for `binary` and `builder`, without
+/// uploading anything. For when the code blob made it on chain but the loader was lost.
+///
+/// Loaders are deterministic, so the result is byte for byte what the original deploy built.
+/// Errors with [`Error::BlobNotFound`] if the code blob isn't on chain after all.
+pub async fn recover_loader(
+    provider: &Provider,
+    builder: &LoaderBuilder,
+    binary: &[u8],
+) -> Result<Vec<u8>> {
+    let blob_id = compute_blob_id(binary)?;
+    if !blob_exists(provider, &blob_id).await? {
+        return Err(Error::BlobNotFound(blob_id));
+    }
+
+    builder.build(binary, &blob_id)
+}
+
+/// Uploads `blob` to the chain `provider` is connected to, paying fees from `account`.
+///
+/// The transaction is signed for the chain id in `provider`'s consensus parameters. Errors with
+/// [`Error::ChainIdMismatch`] if `account` is connected to a different chain, since its coins
+/// wouldn't exist on `provider`'s chain and the node would reject the transaction.
+pub async fn upload_blob(
+    account: &impl Account,
+    provider: &Provider,
+    blob: Blob,
+) -> Result<[u8; 32]> {
+    check_chain_id(account, provider)?;
+
+    let blob_id = blob.id();
+
+    let mut tb = BlobTransactionBuilder::default().with_blob(blob);
+    account.adjust_for_fee(&mut tb, 0).await?;
+    send_blob_tx(account, provider, tb).await?;
+
+    Ok(blob_id)
+}
+
+fn check_chain_id(account: &impl Account, provider: &Provider) -> Result<()> {
+    let chain_id = provider.consensus_parameters().chain_id();
+    let account_chain_id = account.try_provider()?.consensus_parameters().chain_id();
+    if account_chain_id != chain_id {
+        return Err(Error::ChainIdMismatch {
+            expected: chain_id,
+            account: account_chain_id,
+        });
+    }
+
+    Ok(())
+}
+
+/// Signs the funded `tb` with `account` and waits for it to commit on `provider`'s chain.
+async fn send_blob_tx(
+    account: &impl Account,
+    provider: &Provider,
+    mut tb: BlobTransactionBuilder,
+) -> Result<()> {
+    account.add_witnesses(&mut tb)?;
+
+    let tx = tb.build(provider.clone()).await?;
+    provider
+        .send_transaction_and_await_commit(tx)
+        .await?
+        .check(None)?;
+
+    Ok(())
+}
+
+/// Uploads every blob in `blobs` through `account`, returning their ids in order.
+///
+/// A blob transaction carries exactly one blob, so the blobs can't go up atomically: each is
+/// uploaded in its own transaction and an error leaves the earlier ones on chain. Blobs already
+/// on chain are skipped, so retrying after a failure only pays for what is missing.
+pub async fn upload_blobs(account: &impl Account, blobs: &[Blob]) -> Result<Vec<[u8; 32]>> {
+    let provider = account.try_provider()?;
+
+    let mut blob_ids = Vec::with_capacity(blobs.len());
+    for blob in blobs {
+        let blob_id = blob.id();
+        if !blob_exists(provider, &blob_id).await? {
+            upload_blob(account, provider, blob.clone()).await?;
+        }
+        blob_ids.push(blob_id);
+    }
+
+    Ok(blob_ids)
+}
+
+/// Like [`upload_blobs`], with up to `concurrency` uploads in flight at once.
+///
+/// Transactions in flight together must not spend the same coin, so each one is funded by a
+/// different coin of `account` and `account` needs at least `concurrency` coins, each enough for
+/// one upload's fee, to reach full speed. Uploads go in rounds of that many, and every round
+/// waits for the previous one to commit so its change is spendable again.
+///
+/// Errors with [`Error::NoCoins`] if `account` has no base asset coins left to fund a round, and
+/// with [`Error::InsufficientCoin`] if a coin can't cover the fee of the upload it funds.
+pub async fn upload_chunks_parallel(
+    account: &impl Account,
+    blobs: &[Blob],
+    concurrency: usize,
+) -> Result<Vec<[u8; 32]>> {
+    let provider = account.try_provider()?;
+    check_chain_id(account, provider)?;
+    let base_asset_id = *provider.base_asset_id();
+
+    let mut confirmed = HashSet::new();
+    let mut missing = vec![];
+    for blob in blobs {
+        if blob_exists(provider, &blob.id()).await? {
+            confirmed.insert(blob.id());
+        } else {
+            missing.push(blob.clone());
+        }
+    }
+
+    while !missing.is_empty() {
+        let coins = account.get_coins(base_asset_id).await?;
+        if coins.is_empty() {
+            return Err(Error::NoCoins);
+        }
+        let lanes = concurrency.clamp(1, coins.len());
+        let round: Vec<_> = missing.drain(..lanes.min(missing.len())).collect();
+
+        let uploads = round
+            .into_iter()
+            .zip(coins)
+            .map(|(blob, coin)| upload_funded_by(account, provider, blob, coin));
+        confirmed.extend(futures::future::try_join_all(uploads).await?);
+    }
+
+    Ok(blobs
+        .iter()
+        .map(Blob::id)
+        .filter(|blob_id| confirmed.contains(blob_id))
+        .collect())
+}
+
+/// Uploads `blob`, paying its fee from `coin` alone so it can't conflict with other uploads in
+/// flight. Returns the blob id once the upload committed.
+async fn upload_funded_by(
+    account: &impl Account,
+    provider: &Provider,
+    blob: Blob,
+    coin: Coin,
+) -> Result<[u8; 32]> {
+    let blob_id = blob.id();
+    let amount = coin.amount;
+
+    let tb = BlobTransactionBuilder::default()
+        .with_blob(blob)
+        .with_inputs(vec![Input::resource_signed(CoinType::Coin(coin))])
+        .with_outputs(vec![Output::change(
+            account.address().into(),
+            0,
+            *provider.base_asset_id(),
+        )]);
+
+    let fee = tb.estimate_max_fee(provider).await?;
+    if fee > amount {
+        return Err(Error::InsufficientCoin { amount, fee });
+    }
+    let tb = tb.with_tx_policies(TxPolicies::default().with_max_fee(fee));
+
+    send_blob_tx(account, provider, tb).await?;
+
+    Ok(blob_id)
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use fuel_asm::Instruction;
+use fuels::types::{ChainId, TxId};
+
+use crate::LoaderTarget;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("binary is {len} bytes long, too short to contain a data section offset")]
+    BinaryTooShort { len: usize },
+    #[error("data section offset {offset} is out of bounds for a binary of {len} bytes")]
+    InvalidDataOffset { offset: usize, len: usize },
+    #[error("debug log region of {0} bytes doesn't fit in a `movi` immediate")]
+    DebugLogRegionTooLarge(u32),
+    #[error("max copy size {0} must be a non-zero multiple of the word size that fits a `movi`")]
+    InvalidMaxCopySize(u32),
+    #[error("entry offset {0} must be a multiple of the instruction size that fits a `movi`")]
+    InvalidEntryOffset(u32),
+    #[error("loader prologue of {0} bytes is too long to locate the blob id after it")]
+    PrologueTooLong(usize),
+    #[error("binary has a data offset of zero, so there is no code to load")]
+    EmptyCode,
+    #[error("base register {0:#x} is outside the range safe for loaders")]
+    UnsafeBaseRegister(u8),
+    #[error("loader is {len} bytes, {overage} over the chain's max script length of {max}")]
+    LoaderTooLarge { len: u64, max: u64, overage: u64 },
+    #[error("loader is {len} bytes, too large to pad to {pad_to}")]
+    PaddingTooSmall { len: usize, pad_to: usize },
+    #[error("bytecode is not a loader")]
+    NotALoader,
+    #[error("{feature} is not available when targeting {target:?}")]
+    UnsupportedByTarget {
+        target: LoaderTarget,
+        feature: &'static str,
+    },
+    #[error("input already contains a loader")]
+    NestedLoader,
+    #[error("loader contains `{0:?}`, which gas estimation doesn't know about")]
+    UnsupportedInstruction(Instruction),
+    #[error("simulated loader failed: {0}")]
+    SimulationFailed(String),
+    #[error("receipts contain no `ScriptResult`")]
+    MissingScriptResult,
+    #[error("loader metadata is malformed")]
+    InvalidMetadata,
+    #[error("build artifact not found at {0}")]
+    ArtifactNotFound(PathBuf),
+    #[error("setting configurables requires the program's ABI")]
+    MissingAbi,
+    #[error("invalid ABI: {0}")]
+    InvalidAbi(#[from] serde_json::Error),
+    #[error("ABI references unknown type `{0}`")]
+    UnknownAbiType(String),
+    #[error("ABI type `{0}` is not supported as a configurable")]
+    UnsupportedAbiType(String),
+    #[error("ABI declares no configurable named `{0}`")]
+    UnknownConfigurable(String),
+    #[error("value for configurable `{name}` doesn't fit its declared type `{expected}`")]
+    ConfigurableTypeMismatch { name: String, expected: String },
+    #[error("configurable `{name}` is an array of {expected} elements, got {found}")]
+    ArrayLengthMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("configurables left at their compiled-in defaults: {}", .0.join(", "))]
+    UnsetConfigurables(Vec<String>),
+    #[error("configurable `{name}` at offset {offset} lies outside the data section")]
+    ConfigurableOutOfBounds { name: String, offset: usize },
+    #[error("overrides for `{first}` and `{second}` write to the same bytes")]
+    OverlappingOverrides { first: String, second: String },
+    #[error("invalid configurable overrides: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidOverrides(Vec<Error>),
+    #[error("configurable `{0}` doesn't hold the expected override")]
+    OverrideMismatch(String),
+    #[error("data section differs from the binary's at offset {offset}, outside every override")]
+    DataSectionTampered { offset: usize },
+    #[error("account is connected to chain {account:?}, expected chain {expected:?}")]
+    ChainIdMismatch { expected: ChainId, account: ChainId },
+    #[error("account has no base asset coins to pay upload fees with")]
+    NoCoins,
+    #[error("coin of {amount} can't cover an upload fee of {fee}")]
+    InsufficientCoin { amount: u64, fee: u64 },
+    #[error("loader loads its data section from blob {}", hex::encode(.0))]
+    DataSectionInBlob([u8; 32]),
+    #[error("blob {} not found on chain", hex::encode(.0))]
+    BlobNotFound([u8; 32]),
+    #[error("loader and its blob don't reconstruct the expected binary")]
+    BinaryMismatch,
+    #[error("transaction {0} not found")]
+    TransactionNotFound(TxId),
+    #[error("transaction {0} is not a script transaction")]
+    NotAScriptTransaction(TxId),
+    #[error(transparent)]
+    Fuels(#[from] fuels::types::errors::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,372 @@
+use fuel_asm::Instruction;
+use fuels::tx::{GasCosts, Receipt};
+
+use crate::{
+    loader::{debug_log_instruction, decode_prologue, Registers},
+    runtime_data_section, split_binary, Error, LoaderBuilder, Result,
+};
+
+/// Estimates the gas `loader` burns before jumping into the loaded code, assuming its blob holds
+/// `code_len` bytes and none of its checks revert.
+///
+/// Errors with [`Error::DataSectionInBlob`] for a loader built with
+/// [`crate::DataSectionStrategy::Blob`], whose data section length isn't part of the loader.
+/// Price its copy with [`data_section_copy_gas`] instead.
+pub fn estimate_loader_overhead(
+    loader: &[u8],
+    code_len: usize,
+    gas_costs: &GasCosts,
+) -> Result<u64> {
+    let data_section_len = runtime_data_section(loader)?.len();
+    let code_len = code_len as u64;
+    let data_section_len = data_section_len as u64;
+
+    let prologue = decode_prologue(loader)?;
+    let registers = Registers::of(&prologue)?;
+
+    sequence_gas(prologue, registers, code_len, data_section_len, gas_costs)
+}
+
+/// The gas `instructions` of a prologue using `registers` burn on the happy path, with `ldc`s and
+/// `logd`s resolved against `code_len` and `data_section_len` byte copies.
+fn sequence_gas(
+    instructions: Vec<Instruction>,
+    registers: Registers,
+    code_len: u64,
+    data_section_len: u64,
+    gas_costs: &GasCosts,
+) -> Result<u64> {
+    let debug_log = debug_log_instruction(registers);
+
+    let mut instructions = instructions.into_iter();
+    let mut total = 0;
+    let mut last_immediate = 0;
+    while let Some(instruction) = instructions.next() {
+        if let Instruction::MOVI(movi) = instruction {
+            last_immediate = movi.unpack().1.to_u32().into();
+        }
+        total += match instruction {
+            Instruction::MOVE(_) => gas_costs.move_op(),
+            Instruction::MOVI(_) => gas_costs.movi(),
+            Instruction::ADD(_) => gas_costs.add(),
+            Instruction::ADDI(_) => gas_costs.addi(),
+            Instruction::SUB(_) => gas_costs.sub(),
+            Instruction::DIVI(_) => gas_costs.divi(),
+            Instruction::EQ(_) => gas_costs.eq_(),
+            Instruction::LT(_) => gas_costs.lt(),
+            Instruction::LW(_) => gas_costs.lw(),
+            Instruction::LOG(_) => gas_costs.log(),
+            Instruction::JMP(_) => gas_costs.jmp(),
+            Instruction::BSIZ(_) => gas_costs.bsiz().resolve(code_len),
+            Instruction::LDC(ldc) => {
+                let (_, _, _, mode) = ldc.unpack();
+                let len = if mode.to_u8() == 1 {
+                    code_len
+                } else {
+                    data_section_len
+                };
+                gas_costs.ldc().resolve(len)
+            }
+            Instruction::LOGD(_) if instruction == debug_log => {
+                gas_costs.logd().resolve(code_len + data_section_len)
+            }
+            // A debug log region, whose length the preceding `movi` set.
+            Instruction::LOGD(_) => gas_costs.logd().resolve(last_immediate),
+            Instruction::JNZF(jnzf) => {
+                // On the happy path the jump is taken, skipping the revert.
+                let (_, _, skip) = jnzf.unpack();
+                for _ in 0..skip.to_u8() {
+                    instructions.next();
+                }
+                gas_costs.jnzf()
+            }
+            // The chunked copy of `LoaderBuilder::with_max_copy_size`, a loop whose chunk size the
+            // preceding `movi` set. Its other instructions are priced here as well: a `jnzf`, a
+            // `jmpf` taken only without full chunks, the loop body of `ldc`, `add`, `subi` and
+            // `jnzb`, and the `mod` and `ldc` of the leftover bytes.
+            Instruction::DIV(_) => {
+                let chunk = last_immediate.max(1);
+                let full_chunks = data_section_len / chunk;
+                for _ in 0..8 {
+                    instructions.next();
+                }
+                let skip_loop = if full_chunks == 0 {
+                    gas_costs.jmpf()
+                } else {
+                    0
+                };
+                let loop_body = gas_costs.ldc().resolve(chunk)
+                    + gas_costs.add()
+                    + gas_costs.subi()
+                    + gas_costs.jnzb();
+
+                gas_costs.div()
+                    + gas_costs.jnzf()
+                    + skip_loop
+                    + full_chunks * loop_body
+                    + gas_costs.mod_op()
+                    + gas_costs.ldc().resolve(data_section_len % chunk)
+            }
+            _ => return Err(Error::UnsupportedInstruction(instruction)),
+        };
+    }
+
+    Ok(total)
+}
+
+/// The share of a run's gas the loader itself burns, between 0 and 1, given that the loaded code
+/// takes `loaded_code_gas`. See [`estimate_loader_overhead`] for `code_len`.
+///
+/// A run that burns no gas at all has no overhead to speak of, so its ratio is 0.
+pub fn loader_overhead_ratio(
+    loader: &[u8],
+    code_len: usize,
+    gas_costs: &GasCosts,
+    loaded_code_gas: u64,
+) -> Result<f64> {
+    let overhead = estimate_loader_overhead(loader, code_len, gas_costs)?;
+    let total = overhead.saturating_add(loaded_code_gas);
+    if total == 0 {
+        return Ok(0.0);
+    }
+
+    Ok(overhead as f64 / total as f64)
+}
+
+/// The extra gas [`LoaderBuilder::with_debug_log`] adds to every run of the default loader for
+/// `binary`: the `logd` of the code and data section plus the bookkeeping of its length.
+pub fn debug_log_gas_cost(binary: &[u8], gas_costs: &GasCosts) -> Result<u64> {
+    let (code, _) = split_binary(binary)?;
+    let estimate = |debug_log| {
+        let loader = LoaderBuilder::default()
+            .with_debug_log(debug_log)
+            .build(binary, &[0; 32])?;
+        estimate_loader_overhead(&loader, code.len(), gas_costs)
+    };
+
+    Ok(estimate(true)? - estimate(false)?)
+}
+
+/// The gas a loader built by `builder` spends copying a data section of `data_section_len` bytes
+/// onto the stack: reading the length word, stepping over it and the `ldc` of the data itself,
+/// priced from the instructions `builder` actually emits for it. Only the `ldc` scales with the
+/// length.
+pub fn data_section_copy_gas(
+    builder: &LoaderBuilder,
+    data_section_len: usize,
+    gas_costs: &GasCosts,
+) -> Result<u64> {
+    let len = data_section_len as u64;
+
+    // A data section blob is copied with a mode 1 `ldc`, which the code length prices.
+    sequence_gas(
+        builder.load_data_section_sequence(),
+        builder.registers(),
+        len,
+        len,
+        gas_costs,
+    )
+}
+
+/// How a loader run's gas splits between the loader's prologue and the code it loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasBreakdown {
+    pub prologue_gas: u64,
+    pub code_gas: u64,
+}
+
+/// Splits the gas a run of `loader` reports in its `ScriptResult` into the prologue's share,
+/// estimated with [`estimate_loader_overhead`], and whatever is left for the loaded code.
+///
+/// The prologue's share is capped at the reported total, so the two always add up to it.
+pub fn split_gas_usage(
+    receipts: &[Receipt],
+    loader: &[u8],
+    code_len: usize,
+    gas_costs: &GasCosts,
+) -> Result<GasBreakdown> {
+    let total = receipts
+        .iter()
+        .find_map(|receipt| match receipt {
+            Receipt::ScriptResult { gas_used, .. } => Some(*gas_used),
+            _ => None,
+        })
+        .ok_or(Error::MissingScriptResult)?;
+    let prologue_gas = estimate_loader_overhead(loader, code_len, gas_costs)?.min(total);
+
+    Ok(GasBreakdown {
+        prologue_gas,
+        code_gas: total - prologue_gas,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fuels::tx::ScriptExecutionResult;
+
+    use super::*;
+    use crate::test_fixtures::{binary_with_data_section, fixture_binary};
+
+    #[test]
+    fn debug_log_costs_extra_gas() {
+        let binary = fixture_binary();
+        let gas_costs = GasCosts::default();
+
+        let cost = debug_log_gas_cost(&binary, &gas_costs).unwrap();
+
+        let bookkeeping = gas_costs.move_op() + gas_costs.add();
+        assert!(cost > bookkeeping);
+    }
+
+    #[test]
+    fn overhead_grows_with_the_code() {
+        let binary = fixture_binary();
+        let gas_costs = GasCosts::default();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+
+        let small = estimate_loader_overhead(&loader, 1_000, &gas_costs).unwrap();
+        let large = estimate_loader_overhead(&loader, 1_000_000, &gas_costs).unwrap();
+
+        assert!(small > 0);
+        assert!(large > small);
+    }
+
+    #[test]
+    fn data_section_copy_scales_with_its_length() {
+        let gas_costs = GasCosts::default();
+        let builder = LoaderBuilder::default();
+
+        let costs: Vec<_> = [0, 1_000, 100_000]
+            .map(|len| data_section_copy_gas(&builder, len, &gas_costs).unwrap())
+            .to_vec();
+
+        assert!(costs[0] > 0);
+        assert!(costs.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn data_section_copy_is_part_of_the_overhead() {
+        let gas_costs = GasCosts::default();
+        let binary = |data_section_len: usize| binary_with_data_section(&vec![0; data_section_len]);
+
+        for builder in [
+            LoaderBuilder::default(),
+            LoaderBuilder::default().optimize(true),
+            LoaderBuilder::default().with_max_copy_size(16),
+        ] {
+            let overhead = |data_section_len: usize| {
+                let loader = builder.build(&binary(data_section_len), &[0; 32]).unwrap();
+                estimate_loader_overhead(&loader, 16, &gas_costs).unwrap()
+            };
+            let copy = |data_section_len: usize| {
+                data_section_copy_gas(&builder, data_section_len, &gas_costs).unwrap()
+            };
+
+            assert_eq!(overhead(10_000) - overhead(8), copy(10_000) - copy(8));
+        }
+
+        // Optimizing folds one of the two `addi`s stepping over the blob id and length word.
+        let copy = |builder: LoaderBuilder| data_section_copy_gas(&builder, 8, &gas_costs).unwrap();
+        assert_eq!(
+            copy(LoaderBuilder::default()) - copy(LoaderBuilder::default().optimize(true)),
+            gas_costs.addi()
+        );
+    }
+
+    #[test]
+    fn chunked_copy_is_priced_per_chunk() {
+        let gas_costs = GasCosts::default();
+        let builder = LoaderBuilder::default().with_max_copy_size(16);
+        let copy = |len| data_section_copy_gas(&builder, len, &gas_costs).unwrap();
+
+        let per_chunk =
+            gas_costs.ldc().resolve(16) + gas_costs.add() + gas_costs.subi() + gas_costs.jnzb();
+        assert_eq!(copy(160) - copy(16), 9 * per_chunk);
+        // Without a full chunk the loop is jumped over.
+        assert_eq!(
+            copy(8),
+            gas_costs.movi()
+                + gas_costs.div()
+                + gas_costs.jnzf()
+                + gas_costs.jmpf()
+                + gas_costs.mod_op()
+                + gas_costs.ldc().resolve(8)
+                + 2 * gas_costs.addi()
+                + gas_costs.lw()
+        );
+    }
+
+    #[test]
+    fn overhead_of_a_data_blob_loader_is_not_guessed() {
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default()
+            .with_data_section_strategy(crate::DataSectionStrategy::Blob([2; 32]))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let err = estimate_loader_overhead(&loader, 16, &GasCosts::default()).unwrap_err();
+
+        assert!(matches!(err, Error::DataSectionInBlob(id) if id == [2; 32]));
+    }
+
+    #[test]
+    fn overhead_ratio_shrinks_as_the_code_does_more() {
+        let binary = fixture_binary();
+        let code_len = crate::extract_data_offset(&binary).unwrap();
+        let gas_costs = GasCosts::default();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+
+        let ratios: Vec<_> = [0, 1_000, 1_000_000]
+            .map(|code_gas| loader_overhead_ratio(&loader, code_len, &gas_costs, code_gas).unwrap())
+            .to_vec();
+
+        assert_eq!(ratios[0], 1.0);
+        assert!(ratios.iter().all(|ratio| (0.0..=1.0).contains(ratio)));
+        assert!(ratios.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn overhead_ratio_stays_in_range_at_the_extremes() {
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+        let free = GasCosts::free();
+
+        assert_eq!(loader_overhead_ratio(&loader, 0, &free, 0).unwrap(), 0.0);
+
+        let ratio = loader_overhead_ratio(&loader, 1_000, &GasCosts::default(), u64::MAX).unwrap();
+        assert!((0.0..=1.0).contains(&ratio));
+    }
+
+    #[test]
+    fn gas_breakdown_adds_up_to_the_reported_total() {
+        let binary = fixture_binary();
+        let code_len = crate::extract_data_offset(&binary).unwrap();
+        let gas_costs = GasCosts::default();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+        let overhead = estimate_loader_overhead(&loader, code_len, &gas_costs).unwrap();
+        let split = |gas_used| {
+            let receipts = [Receipt::ScriptResult {
+                result: ScriptExecutionResult::Success,
+                gas_used,
+            }];
+            split_gas_usage(&receipts, &loader, code_len, &gas_costs).unwrap()
+        };
+
+        let breakdown = split(overhead + 1_000);
+        assert_eq!(breakdown.prologue_gas, overhead);
+        assert_eq!(breakdown.code_gas, 1_000);
+
+        let breakdown = split(overhead / 2);
+        assert_eq!(breakdown.prologue_gas + breakdown.code_gas, overhead / 2);
+    }
+
+    #[test]
+    fn gas_breakdown_needs_a_script_result() {
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+
+        let err = split_gas_usage(&[], &loader, 16, &GasCosts::default()).unwrap_err();
+
+        assert!(matches!(err, Error::MissingScriptResult));
+    }
+}
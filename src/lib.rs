@@ -1,112 +1,129 @@
+mod artifacts;
+mod blob;
+mod configurables;
+mod deploy;
+mod error;
+mod gas;
+mod loader;
+mod receipts;
+mod simulate;
+#[cfg(test)]
+mod test_fixtures;
+
+pub use artifacts::{
+    discover_artifacts, loader_from_project, write_loader, LoaderFile, LoaderWriteOptions,
+    ProjectArtifacts,
+};
+pub use blob::{
+    compute_blob_id, compute_blob_id_with, loader_blob, loader_id, same_blob, BlobIdHasher,
+    VmBlobIdHasher,
+};
+pub use configurables::{
+    apply_configurables, assert_all_configurables_set, audit_configurables,
+    configurable_offsets_from_abi, default_configurables, read_configurable, read_configurables,
+    validate_overrides, verify_only_overrides_changed, ConfigurableAudit, ConfigurableSlot,
+    ConfigurableType, ConfigurableValue,
+};
+pub use deploy::{
+    blob_exists, deploy_contract_loader, deploy_loader, loader_contract_id, loader_contract_salt,
+    loader_from_tx, needs_loader, recover_loader, upload_blob, upload_blobs,
+    upload_chunks_parallel, validate_loader_fits, verify_loader_on_chain,
+};
+pub use error::{Error, Result};
+pub use gas::{
+    data_section_copy_gas, debug_log_gas_cost, estimate_loader_overhead, loader_overhead_ratio,
+    split_gas_usage, GasBreakdown,
+};
+pub use loader::{
+    build_offline, contains_nested_loader, extract_data_offset, find_blob_id, has_debug_log,
+    is_loader, multi_blob_ids, parse_loader, prologue_byte_len, reconstruct_binary,
+    runtime_data_section, split_binary, strip_debug_log, transform_into_configurable_loader,
+    DataSectionStrategy, DebugLogBase, Loader, LoaderBuilder, LoaderTarget, ParsedLoader,
+    CODE_SIZE_MISMATCH_REVERT_CODE, DEFAULT_BASE_REGISTER, GAS_MARKER_BEFORE_JUMP,
+    GAS_MARKER_BLOB_LOADED, GAS_MARKER_DATA_SECTION_LOADED, JUMP_GUARD_REVERT_CODE,
+    LOADER_REGISTER_COUNT, MAX_IMMEDIATE, SAFE_BASE_REGISTERS,
+};
+pub use receipts::{
+    expected_loader_receipts, extract_log_data_by, matches_loader_run, LoaderReceipt, LogSelector,
+    ReceiptPattern,
+};
+pub use simulate::{simulate_loader, LoaderRegisterValues, PrologueState, SIMULATED_IS};
+
 #[cfg(test)]
 mod tests {
-    use fuel_asm::{op, Instruction, RegId};
+    use fuel_asm::{op, GTFArgs, RegId};
     use fuels::{
-        accounts::Account,
-        core::constants::WORD_SIZE,
+        accounts::{predicate::Predicate, wallet::WalletUnlocked, Account, ViewOnlyAccount},
         macros::setup_program_test,
-        tx::Receipt,
+        test_helpers::{
+            launch_custom_provider_and_get_wallets, launch_provider_and_get_wallet, ChainConfig,
+            WalletsConfig,
+        },
+        tx::{
+            Bytes32, ConsensusParameters, ContractId, Output, Receipt, ScriptParameters, TxPointer,
+            UtxoId,
+        },
         types::{
-            transaction_builders::{Blob, BlobTransactionBuilder},
-            Bits256, U256,
+            errors::{transaction::Reason, Error as FuelsError},
+            input::Input,
+            transaction::{Transaction, TxPolicies},
+            transaction_builders::{
+                Blob, BlobTransactionBuilder, BuildableTransaction, ScriptTransactionBuilder,
+            },
+            Bits256, ChainId, SizedAsciiString, U256,
         },
     };
 
-    fn extract_data_offset(binary: &[u8]) -> usize {
-        let data_offset: [u8; 8] = binary[8..16].try_into().expect("checked above");
-
-        u64::from_be_bytes(data_offset) as usize
-    }
-
-    fn transform_into_configurable_loader(binary: Vec<u8>, blob_id: &[u8; 32]) -> Vec<u8> {
-        // The final code is going to have this structure (if the data section is non-empty):
-        // 1. loader instructions
-        // 2. blob id
-        // 3. length_of_data_section
-        // 4. the data_section (updated with configurables as needed)
-        const BLOB_ID_SIZE: u16 = 32;
-        const REG_ADDRESS_OF_DATA_AFTER_CODE: u8 = 0x10;
-        const REG_START_OF_LOADED_CODE: u8 = 0x11;
-        const REG_GENERAL_USE: u8 = 0x12;
-        const REG_START_OF_DATA_SECTION: u8 = 0x13;
-        let get_instructions = |num_of_instructions| {
-            // There are 3 main steps:
-            // 1. Load the blob content into memory
-            // 2. Load the data section right after the blob
-            // 3. Jump to the beginning of the memory where the blob was loaded
-            [
-                // 1. Load the blob content into memory
-                // Find the start of the hardcoded blob ID, which is located after the loader code ends.
-                op::move_(REG_ADDRESS_OF_DATA_AFTER_CODE, RegId::PC),
-                // hold the address of the blob ID.
-                op::addi(
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    num_of_instructions * Instruction::SIZE as u16,
-                ),
-                // The code is going to be loaded from the current value of SP onwards, save
-                // the location into REG_START_OF_LOADED_CODE so we can jump into it at the end.
-                op::move_(REG_START_OF_LOADED_CODE, RegId::SP),
-                // REG_GENERAL_USE to hold the size of the blob.
-                op::bsiz(REG_GENERAL_USE, REG_ADDRESS_OF_DATA_AFTER_CODE),
-                op::move_(0x16, REG_GENERAL_USE),
-                // Push the blob contents onto the stack.
-                op::ldc(REG_ADDRESS_OF_DATA_AFTER_CODE, 0, REG_GENERAL_USE, 1),
-                // Move on to the data section length
-                op::addi(
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    BLOB_ID_SIZE,
-                ),
-                // load the size of the data section into REG_GENERAL_USE
-                op::lw(REG_GENERAL_USE, REG_ADDRESS_OF_DATA_AFTER_CODE, 0),
-                // after we have read the length of the data section, we move the pointer to the actual
-                // data by skipping WORD_SIZE B.
-                op::addi(
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    REG_ADDRESS_OF_DATA_AFTER_CODE,
-                    WORD_SIZE as u16,
-                ),
-                // load the data section
-                op::ldc(REG_START_OF_DATA_SECTION, 0, REG_GENERAL_USE, 2),
-                op::add(0x16, 0x16, REG_GENERAL_USE),
-                op::logd(RegId::ZERO, RegId::ZERO, REG_START_OF_LOADED_CODE, 0x16),
-                // Jump into the memory where the contract is loaded.
-                // What follows is called _jmp_mem by the sway compiler.
-                // Subtract the address contained in IS because jmp will add it back.
-                op::sub(
-                    REG_START_OF_LOADED_CODE,
-                    REG_START_OF_LOADED_CODE,
-                    RegId::IS,
-                ),
-                // jmp will multiply by 4, so we need to divide to cancel that out.
-                op::divi(REG_START_OF_LOADED_CODE, REG_START_OF_LOADED_CODE, 4),
-                // Jump to the start of the contract we loaded.
-                op::jmp(REG_START_OF_LOADED_CODE),
-            ]
-        };
-
-        let offset = extract_data_offset(&binary);
+    use super::*;
+    use crate::test_fixtures::{fixture_abi, fixture_binary};
 
-        let data_section = binary[offset..].to_vec();
+    async fn upload_code_blob(wallet: &WalletUnlocked, binary: &[u8]) -> [u8; 32] {
+        let data_section_offset = extract_data_offset(binary).unwrap();
+        let blob = Blob::new(binary[..data_section_offset].to_vec());
 
-        let num_of_instructions = u16::try_from(get_instructions(0).len())
-            .expect("to never have more than u16::MAX instructions");
+        upload_blob(wallet, wallet.provider().unwrap(), blob)
+            .await
+            .unwrap()
+    }
 
-        let instruction_bytes = get_instructions(num_of_instructions)
+    /// A bare `ret(1)` with an empty data section, for when the fixture script can't be used.
+    fn ret_one_binary() -> Vec<u8> {
+        [op::ret(RegId::ONE), op::noop()]
             .into_iter()
-            .flat_map(|instruction| instruction.to_bytes());
+            .flat_map(|instruction| instruction.to_bytes())
+            .chain(16u64.to_be_bytes())
+            .collect()
+    }
 
-        let blob_bytes = blob_id.iter().copied();
+    type ScriptOutput = (
+        bool,
+        u8,
+        u16,
+        u32,
+        u64,
+        U256,
+        Bits256,
+        SizedAsciiString<4>,
+        (u8, bool),
+    );
 
-        let data_section_len: u64 = u64::try_from(data_section.len())
-            .expect("to never have more than u64::MAX data section length");
+    /// What the fixture script returns when none of its configurables are overridden.
+    fn default_script_output() -> ScriptOutput {
+        (
+            true,
+            8,
+            16,
+            32,
+            63,
+            U256::from(8),
+            Bits256([1; 32]),
+            "fuel".try_into().unwrap(),
+            (8, true),
+        )
+    }
 
-        instruction_bytes
-            .chain(blob_bytes)
-            .chain(data_section_len.to_be_bytes())
-            .chain(data_section)
-            .collect()
+    fn write_loader_file(loader: &[u8]) -> LoaderFile {
+        write_loader(loader, &LoaderWriteOptions::default()).unwrap()
     }
 
     #[tokio::test]
@@ -120,7 +137,7 @@ mod tests {
 
         let binary = std::fs::read("./script/out/release/script.bin").unwrap();
 
-        let data_section_offset = extract_data_offset(&binary);
+        let data_section_offset = extract_data_offset(&binary).unwrap();
 
         let without_data_section = binary[..data_section_offset].to_vec();
         let blob = Blob::new(without_data_section);
@@ -141,7 +158,7 @@ mod tests {
         let loader_file = temp_file.path().join("loader.bin");
         std::fs::write(
             &loader_file,
-            transform_into_configurable_loader(binary, &blob_id),
+            transform_into_configurable_loader(&binary, &blob_id).unwrap(),
         )
         .unwrap();
         let my_script = MyScript::new(wallet.clone(), loader_file.to_str().unwrap());
@@ -180,4 +197,835 @@ mod tests {
 
         pretty_assertions::assert_eq!(response.value, expected_value);
     }
+
+    #[tokio::test]
+    async fn gas_markers_are_logged_at_each_phase() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let loader = LoaderBuilder::default()
+            .with_gas_markers(true)
+            .build(&binary, &blob_id)
+            .unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        let markers: Vec<_> = response
+            .receipts
+            .iter()
+            .filter_map(|receipt| match receipt {
+                Receipt::Log { ra, rb, .. } => Some((*ra, *rb)),
+                _ => None,
+            })
+            .collect();
+
+        let ids: Vec<_> = markers.iter().map(|(id, _)| *id).collect();
+        assert_eq!(
+            ids,
+            [
+                GAS_MARKER_BLOB_LOADED,
+                GAS_MARKER_DATA_SECTION_LOADED,
+                GAS_MARKER_BEFORE_JUMP
+            ]
+            .map(u64::from)
+        );
+
+        // Remaining gas only ever goes down between phases.
+        assert!(markers.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+        // The markers don't change what the loaded code sees.
+        pretty_assertions::assert_eq!(response.value, default_script_output());
+    }
+
+    #[tokio::test]
+    async fn code_size_mismatch_reverts() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let code_size = extract_data_offset(&binary).unwrap() as u64;
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let run_expecting = |size| {
+            let loader = LoaderBuilder::default()
+                .with_expected_code_size(size)
+                .build(&binary, &blob_id)
+                .unwrap();
+            let loader_file = write_loader_file(&loader);
+            let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+            async move {
+                let _loader_file = loader_file;
+                my_script.main().call().await
+            }
+        };
+
+        let response = run_expecting(code_size).await.unwrap();
+        pretty_assertions::assert_eq!(response.value, default_script_output());
+
+        let err = run_expecting(code_size + 8).await.unwrap_err();
+        assert!(matches!(
+            err,
+            FuelsError::Transaction(Reason::Reverted { revert_id, .. })
+                if revert_id == u64::from(CODE_SIZE_MISMATCH_REVERT_CODE)
+        ));
+    }
+
+    #[tokio::test]
+    async fn fixed_blob_size_runs_code_from_an_oversized_blob() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let padded_blob = Blob::new([code, &[0; 64]].concat());
+        let blob_id = upload_blob(&wallet, wallet.provider().unwrap(), padded_blob)
+            .await
+            .unwrap();
+
+        let loader = LoaderBuilder::default()
+            .with_expected_blob_size(code.len() as u64)
+            .build(&binary, &blob_id)
+            .unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        pretty_assertions::assert_eq!(response.value, default_script_output());
+    }
+
+    #[tokio::test]
+    async fn optimized_loader_runs_the_same() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        for builder in [
+            LoaderBuilder::default(),
+            LoaderBuilder::default().with_debug_log(true),
+        ] {
+            let plain = builder.build(&binary, &blob_id).unwrap();
+            let optimized = builder.optimize(true).build(&binary, &blob_id).unwrap();
+            assert!(prologue_byte_len(&optimized).unwrap() < prologue_byte_len(&plain).unwrap());
+
+            let run = |loader: Vec<u8>| {
+                let loader_file = write_loader_file(&loader);
+                let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+                async move {
+                    let _loader_file = loader_file;
+                    my_script.main().call().await.unwrap()
+                }
+            };
+            let plain = run(plain).await;
+            let optimized = run(optimized).await;
+
+            let logs = |receipts: &[Receipt]| extract_log_data_by(receipts, LogSelector::Index(0));
+            assert_eq!(logs(&optimized.receipts), logs(&plain.receipts));
+            pretty_assertions::assert_eq!(optimized.value, plain.value);
+            pretty_assertions::assert_eq!(optimized.value, default_script_output());
+        }
+    }
+
+    #[tokio::test]
+    async fn metadata_does_not_affect_execution() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let loader = LoaderBuilder::default()
+            .with_debug_log(true)
+            .with_metadata("built from script/src/main.sw")
+            .build(&binary, &blob_id)
+            .unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        let log_data = extract_log_data_by(&response.receipts, LogSelector::Index(0)).unwrap();
+        assert_eq!(log_data, binary);
+        pretty_assertions::assert_eq!(response.value, default_script_output());
+    }
+
+    #[tokio::test]
+    async fn overridden_configurable_changes_the_script_result() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let abi = fixture_abi();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let loader = LoaderBuilder::default()
+            .with_abi(abi)
+            .set_configurable("U64", ConfigurableValue::U64(1234))
+            .build(&binary, &blob_id)
+            .unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        let mut expected = default_script_output();
+        expected.4 = 1234;
+        pretty_assertions::assert_eq!(response.value, expected);
+    }
+
+    #[tokio::test]
+    async fn loads_the_data_section_from_its_own_blob() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let abi = fixture_abi();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let builder = LoaderBuilder::default()
+            .with_abi(abi)
+            .set_configurable("U64", ConfigurableValue::U64(1234));
+        let data_section = builder.configured_data_section(&binary).unwrap();
+        let data_blob_id =
+            upload_blob(&wallet, wallet.provider().unwrap(), Blob::new(data_section))
+                .await
+                .unwrap();
+
+        let loader = builder
+            .with_data_section_strategy(DataSectionStrategy::Blob(data_blob_id))
+            .with_debug_log(true)
+            .build(&binary, &blob_id)
+            .unwrap();
+        assert!(matches!(
+            runtime_data_section(&loader),
+            Err(Error::DataSectionInBlob(id)) if id == data_blob_id
+        ));
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        let log_data = extract_log_data_by(&response.receipts, LogSelector::Index(0)).unwrap();
+        let (code, _) = split_binary(&binary).unwrap();
+        assert_eq!(&log_data[..code.len()], code);
+        let mut expected = default_script_output();
+        expected.4 = 1234;
+        pretty_assertions::assert_eq!(response.value, expected);
+    }
+
+    #[tokio::test]
+    async fn verifies_a_loader_with_its_data_section_in_a_blob() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let provider = wallet.provider().unwrap();
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let builder = LoaderBuilder::default();
+        let data_section = builder.configured_data_section(&binary).unwrap();
+        let loader = builder
+            .clone()
+            .with_data_section_strategy(DataSectionStrategy::Blob(
+                Blob::new(data_section.clone()).id(),
+            ))
+            .build(&binary, &blob_id)
+            .unwrap();
+        assert!(matches!(
+            verify_loader_on_chain(provider, &loader, &binary).await,
+            Err(Error::BlobNotFound(_))
+        ));
+
+        upload_blob(&wallet, provider, Blob::new(data_section))
+            .await
+            .unwrap();
+
+        verify_loader_on_chain(provider, &loader, &binary)
+            .await
+            .unwrap();
+        let mut tampered = binary.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            verify_loader_on_chain(provider, &loader, &tampered).await,
+            Err(Error::BinaryMismatch)
+        ));
+    }
+
+    async fn launch_wallet_on_chain(chain_id: u64) -> WalletUnlocked {
+        let mut consensus_parameters = ConsensusParameters::default();
+        consensus_parameters.set_chain_id(ChainId::new(chain_id));
+        let chain_config = ChainConfig {
+            consensus_parameters,
+            ..ChainConfig::local_testnet()
+        };
+
+        let mut wallets = launch_custom_provider_and_get_wallets(
+            WalletsConfig::new(Some(1), None, None),
+            None,
+            Some(chain_config),
+        )
+        .await
+        .unwrap();
+
+        wallets.pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn uploads_blobs_on_a_custom_chain() {
+        let wallet = launch_wallet_on_chain(1337).await;
+        let provider = wallet.provider().unwrap();
+        assert_eq!(
+            provider.consensus_parameters().chain_id(),
+            ChainId::new(1337)
+        );
+
+        let blob = Blob::new(vec![1; 64]);
+        let expected_id = blob.id();
+
+        let blob_id = upload_blob(&wallet, provider, blob).await.unwrap();
+
+        assert_eq!(blob_id, expected_id);
+    }
+
+    #[tokio::test]
+    async fn rejects_accounts_on_another_chain() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let other_chain = launch_wallet_on_chain(1337).await;
+
+        let err = upload_blob(
+            &wallet,
+            other_chain.provider().unwrap(),
+            Blob::new(vec![1; 64]),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::ChainIdMismatch { expected, .. } if expected == ChainId::new(1337)
+        ));
+    }
+
+    #[tokio::test]
+    async fn loader_can_run_repeatedly() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let loader = LoaderBuilder::default().build(&binary, &blob_id).unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        // Each call is its own transaction, the loader keeps no state between them.
+        let first = my_script.main().call().await.unwrap();
+        let second = my_script.main().call().await.unwrap();
+
+        assert_ne!(first.tx_id, second.tx_id);
+        pretty_assertions::assert_eq!(first.value, default_script_output());
+        pretty_assertions::assert_eq!(second.value, default_script_output());
+    }
+
+    #[tokio::test]
+    async fn reuses_a_blob_uploaded_by_another_account() {
+        setup_program_test!(
+            Wallets("uploader", "deployer"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&uploader, &binary).await;
+
+        let provider = deployer.provider().unwrap();
+        assert!(blob_exists(provider, &blob_id).await.unwrap());
+
+        let base_asset_id = *provider.base_asset_id();
+        let balance_before = deployer.get_asset_balance(&base_asset_id).await.unwrap();
+
+        let loader = deploy_loader(&deployer, &LoaderBuilder::default(), &binary)
+            .await
+            .unwrap();
+
+        // Nothing was uploaded, so the deployer paid no fees.
+        let balance_after = deployer.get_asset_balance(&base_asset_id).await.unwrap();
+        assert_eq!(balance_before, balance_after);
+        assert_eq!(find_blob_id(&loader).unwrap(), blob_id);
+
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(deployer.clone(), loader_file.path().to_str().unwrap());
+        let response = my_script.main().call().await.unwrap();
+        pretty_assertions::assert_eq!(response.value, default_script_output());
+    }
+
+    #[tokio::test]
+    async fn fixture_receipts_match_the_expected_pattern() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let builder = LoaderBuilder::default()
+            .with_debug_log(true)
+            .with_gas_markers(true);
+        let loader_file = write_loader_file(&builder.build(&binary, &blob_id).unwrap());
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+
+        assert!(matches_loader_run(
+            &response.receipts,
+            &expected_loader_receipts(&builder)
+        ));
+        assert!(!matches_loader_run(
+            &response.receipts,
+            &expected_loader_receipts(&LoaderBuilder::default().with_debug_log(true))
+        ));
+    }
+
+    #[tokio::test]
+    async fn recovers_the_loader_from_its_transaction() {
+        setup_program_test!(
+            Wallets("wallet"),
+            Abigen(Script(name = "MyScript", project = "script")),
+        );
+
+        let binary = fixture_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let loader = LoaderBuilder::default()
+            .with_metadata("recover me")
+            .build(&binary, &blob_id)
+            .unwrap();
+        let loader_file = write_loader_file(&loader);
+        let my_script = MyScript::new(wallet.clone(), loader_file.path().to_str().unwrap());
+
+        let response = my_script.main().call().await.unwrap();
+        let tx_id = response.tx_id.expect("script call has a tx id");
+
+        let recovered = loader_from_tx(wallet.provider().unwrap(), &tx_id)
+            .await
+            .unwrap();
+        assert_eq!(recovered, loader);
+        assert_eq!(
+            parse_loader(&recovered).unwrap().metadata,
+            Some("recover me")
+        );
+    }
+
+    #[tokio::test]
+    async fn uploads_several_blobs() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let provider = wallet.provider().unwrap();
+        let blobs: Vec<_> = (1..=3).map(|byte| Blob::new(vec![byte; 64])).collect();
+        let expected_ids: Vec<[u8; 32]> = blobs.iter().map(Blob::id).collect();
+
+        let blob_ids = upload_blobs(&wallet, &blobs).await.unwrap();
+
+        assert_eq!(blob_ids, expected_ids);
+        for blob_id in &blob_ids {
+            assert!(blob_exists(provider, blob_id).await.unwrap());
+        }
+
+        // Everything is on chain already, so a retry uploads nothing.
+        let base_asset_id = *provider.base_asset_id();
+        let balance = wallet.get_asset_balance(&base_asset_id).await.unwrap();
+        assert_eq!(upload_blobs(&wallet, &blobs).await.unwrap(), expected_ids);
+        assert_eq!(
+            wallet.get_asset_balance(&base_asset_id).await.unwrap(),
+            balance
+        );
+    }
+
+    async fn launch_wallet_with_max_script_length(max_script_length: u64) -> WalletUnlocked {
+        let mut consensus_parameters = ConsensusParameters::default();
+        consensus_parameters
+            .set_script_params(ScriptParameters::DEFAULT.with_max_script_length(max_script_length));
+        let chain_config = ChainConfig {
+            consensus_parameters,
+            ..ChainConfig::local_testnet()
+        };
+        let mut wallets = launch_custom_provider_and_get_wallets(
+            WalletsConfig::new(Some(1), None, None),
+            None,
+            Some(chain_config),
+        )
+        .await
+        .unwrap();
+
+        wallets.pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn needs_a_loader_only_above_the_script_size_limit() {
+        let max_script_length = 1024;
+        let wallet = launch_wallet_with_max_script_length(max_script_length).await;
+        let provider = wallet.provider().unwrap();
+
+        let binary = |len| vec![0; len as usize];
+        assert!(!needs_loader(provider, &binary(max_script_length))
+            .await
+            .unwrap());
+        assert!(needs_loader(provider, &binary(max_script_length + 1))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn rejects_loaders_over_the_script_size_limit() {
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+        let len = loader.len() as u64;
+
+        let wallet = launch_wallet_with_max_script_length(len).await;
+        validate_loader_fits(wallet.provider().unwrap(), &loader)
+            .await
+            .unwrap();
+
+        let wallet = launch_wallet_with_max_script_length(len - 1).await;
+        let err = validate_loader_fits(wallet.provider().unwrap(), &loader)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LoaderTooLarge { overage: 1, max, .. } if max == len - 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn deploys_a_contract_loader_at_a_predictable_address() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let binary = fixture_binary();
+        let blob_id = compute_blob_id(&binary).unwrap();
+
+        let salt = loader_contract_salt(&blob_id, b"v1");
+        assert_eq!(salt, loader_contract_salt(&blob_id, b"v1"));
+        assert_ne!(salt, loader_contract_salt(&blob_id, b"v2"));
+        assert_ne!(salt, loader_contract_salt(&[0; 32], b"v1"));
+
+        let builder = LoaderBuilder::default().with_target(LoaderTarget::Contract);
+        let loader = builder.build(&binary, &blob_id).unwrap();
+        let predicted = loader_contract_id(&loader, salt);
+        assert_eq!(predicted, loader_contract_id(&loader, salt));
+
+        let contract_id = deploy_contract_loader(&wallet, &builder, &binary, b"v1")
+            .await
+            .unwrap();
+
+        assert_eq!(contract_id, predicted);
+    }
+
+    #[tokio::test]
+    async fn calls_a_contract_loader() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let builder = LoaderBuilder::default();
+
+        let contract_id = deploy_contract_loader(&wallet, &builder, &ret_one_binary(), b"v1")
+            .await
+            .unwrap();
+
+        let receipts = call_raw_contract(&wallet, contract_id).await.unwrap();
+        assert!(receipts.iter().any(|receipt| matches!(
+            receipt,
+            Receipt::Return { id, val: 1, .. } if *id == contract_id
+        )));
+    }
+
+    #[tokio::test]
+    async fn spends_a_coin_locked_by_a_predicate_loader() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let provider = wallet.provider().unwrap();
+        let asset_id = *provider.consensus_parameters().base_asset_id();
+
+        let binary = ret_one_binary();
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+        let loader = LoaderBuilder::default()
+            .with_target(LoaderTarget::Predicate)
+            .build(&binary, &blob_id)
+            .unwrap();
+        let predicate = Predicate::from_code(loader).with_provider(provider.clone());
+
+        wallet
+            .transfer(predicate.address(), 1000, asset_id, TxPolicies::default())
+            .await
+            .unwrap();
+
+        // The predicate pays the fee too, so the spend only goes through if the loaded code ran.
+        predicate
+            .transfer(wallet.address(), 500, asset_id, TxPolicies::default())
+            .await
+            .unwrap();
+        assert!(predicate.get_asset_balance(&asset_id).await.unwrap() < 500);
+    }
+
+    #[tokio::test]
+    async fn offline_build_matches_the_deployed_loader() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let binary = fixture_binary();
+        let abi = fixture_abi();
+        let overrides = [("U64".to_string(), ConfigurableValue::U64(1234))];
+
+        let offline = build_offline(
+            &binary,
+            &compute_blob_id(&binary).unwrap(),
+            &overrides,
+            &abi,
+        )
+        .unwrap();
+
+        let builder = LoaderBuilder::default()
+            .with_abi(abi)
+            .set_configurable("U64", ConfigurableValue::U64(1234));
+        let deployed = deploy_loader(&wallet, &builder, &binary).await.unwrap();
+        assert_eq!(offline.as_bytes(), deployed);
+    }
+
+    #[tokio::test]
+    async fn verifies_a_loader_against_its_blob_on_chain() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let provider = wallet.provider().unwrap();
+        let binary = fixture_binary();
+
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+        let loader = LoaderBuilder::default().build(&binary, &blob_id).unwrap();
+        verify_loader_on_chain(provider, &loader, &binary)
+            .await
+            .unwrap();
+
+        // A loader pointing at a blob with different code doesn't reconstruct the binary.
+        let mut tampered = binary.clone();
+        tampered[100] ^= 0xFF;
+        let tampered_id = upload_code_blob(&wallet, &tampered).await;
+        let loader = LoaderBuilder::default()
+            .build(&binary, &tampered_id)
+            .unwrap();
+        let err = verify_loader_on_chain(provider, &loader, &binary)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BinaryMismatch));
+
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+        let err = verify_loader_on_chain(provider, &loader, &binary)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BlobNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn recovers_a_loader_after_a_partial_deploy() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let provider = wallet.provider().unwrap();
+        let binary = fixture_binary();
+        let builder = LoaderBuilder::default().with_metadata("ci build");
+
+        let err = recover_loader(provider, &builder, &binary)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BlobNotFound(_)));
+
+        // The deploy crashed right after the blob was committed, the loader was never saved.
+        let blob_id = upload_code_blob(&wallet, &binary).await;
+
+        let recovered = recover_loader(provider, &builder, &binary).await.unwrap();
+        assert_eq!(recovered, builder.build(&binary, &blob_id).unwrap());
+    }
+
+    /// Calls `contract_id` from a raw script, with no function selector or arguments.
+    async fn call_raw_contract(
+        wallet: &WalletUnlocked,
+        contract_id: ContractId,
+    ) -> Result<Vec<Receipt>> {
+        let provider = wallet.provider().unwrap();
+        let call_frame = 0x10;
+        let script = [
+            op::gtf_args(call_frame, RegId::ZERO, GTFArgs::ScriptData),
+            op::call(call_frame, RegId::ZERO, RegId::ZERO, RegId::CGAS),
+            op::ret(RegId::ONE),
+        ]
+        .into_iter()
+        .flat_map(|instruction| instruction.to_bytes())
+        .collect();
+        // The contract id followed by the two call parameters, left zeroed.
+        let script_data = [contract_id.to_vec(), vec![0; 16]].concat();
+
+        let input = Input::contract(
+            UtxoId::default(),
+            Bytes32::zeroed(),
+            Bytes32::zeroed(),
+            TxPointer::default(),
+            contract_id,
+        );
+        let output = Output::contract(0, Bytes32::zeroed(), Bytes32::zeroed());
+        let mut tb = ScriptTransactionBuilder::prepare_transfer(
+            vec![input],
+            vec![output],
+            TxPolicies::default(),
+        )
+        .with_script(script)
+        .with_script_data(script_data);
+        wallet.adjust_for_fee(&mut tb, 0).await?;
+        wallet.add_witnesses(&mut tb)?;
+
+        let tx = tb.build(provider.clone()).await?;
+        Ok(provider
+            .send_transaction_and_await_commit(tx)
+            .await?
+            .take_receipts_checked(None)?)
+    }
+
+    /// Runs a `ret(1)` loader with `data_section_len` bytes of data section as a plain script.
+    /// Returns `false` if the loader or its transaction is over the node's size limits.
+    async fn runs_with_data_section(
+        wallet: &WalletUnlocked,
+        code: &[u8],
+        blob_id: &[u8; 32],
+        data_section_len: usize,
+    ) -> bool {
+        let provider = wallet.provider().unwrap();
+        let binary = [code.to_vec(), vec![0; data_section_len]].concat();
+        let loader = LoaderBuilder::default().build(&binary, blob_id).unwrap();
+
+        if let Err(err) = validate_loader_fits(provider, &loader).await {
+            assert!(matches!(err, Error::LoaderTooLarge { .. }), "{err}");
+            return false;
+        }
+
+        let mut tb =
+            ScriptTransactionBuilder::prepare_transfer(vec![], vec![], TxPolicies::default())
+                .with_script(loader);
+        wallet.adjust_for_fee(&mut tb, 0).await.unwrap();
+        wallet.add_witnesses(&mut tb).unwrap();
+        let tx = tb.build(provider.clone()).await.unwrap();
+
+        // The node also caps the size of the whole transaction, which can bind before
+        // `max_script_length` does.
+        let max_tx_size = provider.consensus_parameters().tx_params().max_size();
+        if tx.size() as u64 > max_tx_size {
+            return false;
+        }
+
+        let receipts = provider
+            .send_transaction_and_await_commit(tx)
+            .await
+            .unwrap()
+            .take_receipts_checked(None)
+            .unwrap_or_else(|err| {
+                panic!("a data section of {data_section_len} bytes failed: {err}")
+            });
+        assert!(
+            receipts
+                .iter()
+                .any(|receipt| matches!(receipt, Receipt::Return { val: 1, .. })),
+            "a data section of {data_section_len} bytes didn't return 1"
+        );
+
+        true
+    }
+
+    // There is no forc here to compile a fixture with a huge data section, so the code is a bare
+    // `ret(1)` and the data section is filler it never reads. That still exercises everything the
+    // loader does with the data section: the length word, the `ldc` copy and the stack growth.
+    #[tokio::test]
+    #[ignore = "uploads and runs loaders with data sections up to the script size limit"]
+    async fn runs_loaders_with_large_data_sections() {
+        let wallet = launch_provider_and_get_wallet().await.unwrap();
+        let max_script_length = wallet
+            .provider()
+            .unwrap()
+            .consensus_parameters()
+            .script_params()
+            .max_script_length() as usize;
+
+        let code = ret_one_binary();
+        let blob_id = upload_code_blob(&wallet, &code).await;
+        assert!(runs_with_data_section(&wallet, &code, &blob_id, 0).await);
+
+        // The loader is longer than its data section, so a full script's worth never fits. Bisect
+        // down to the word between the largest data section that runs and the smallest that
+        // doesn't.
+        let (mut largest, mut smallest_rejected) = (0, max_script_length.next_multiple_of(8));
+        while smallest_rejected - largest > 8 {
+            let len = (largest + smallest_rejected) / 16 * 8;
+            if runs_with_data_section(&wallet, &code, &blob_id, len).await {
+                largest = len;
+            } else {
+                smallest_rejected = len;
+            }
+        }
+
+        let empty_loader_len = LoaderBuilder::default()
+            .build(&code, &blob_id)
+            .unwrap()
+            .len();
+        let ceiling = max_script_length - empty_loader_len;
+        println!("largest data section that ran: {largest} of at most {ceiling} bytes");
+        assert!(
+            largest > 0 && largest <= ceiling,
+            "the largest data section that ran was {largest} bytes, the script limit of \
+             {max_script_length} bytes leaves room for at most {ceiling}"
+        );
+    }
+
+    #[tokio::test]
+    async fn uploads_chunks_in_parallel() {
+        let mut wallets = launch_custom_provider_and_get_wallets(
+            WalletsConfig::new(Some(1), Some(4), Some(1_000_000_000)),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let wallet = wallets.pop().unwrap();
+        let provider = wallet.provider().unwrap();
+
+        let blobs: Vec<_> = (1..=6).map(|byte| Blob::new(vec![byte; 128])).collect();
+        let mut expected_ids: Vec<[u8; 32]> = blobs.iter().map(Blob::id).collect();
+
+        let mut blob_ids = upload_chunks_parallel(&wallet, &blobs, 3).await.unwrap();
+
+        for blob_id in &blob_ids {
+            assert!(blob_exists(provider, blob_id).await.unwrap());
+        }
+        blob_ids.sort();
+        expected_ids.sort();
+        assert_eq!(blob_ids, expected_ids);
+    }
+
+    #[tokio::test]
+    async fn parallel_uploads_need_coins() {
+        let funded = launch_provider_and_get_wallet().await.unwrap();
+        let wallet = WalletUnlocked::new_random(Some(funded.provider().unwrap().clone()));
+
+        let err = upload_chunks_parallel(&wallet, &[Blob::new(vec![1; 128])], 3)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::NoCoins));
+    }
 }
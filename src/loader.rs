@@ -0,0 +1,2130 @@
+use std::ops::RangeInclusive;
+
+use fuel_asm::{op, Instruction, RegId};
+use fuels::core::constants::WORD_SIZE;
+
+use crate::{
+    configurables::{apply_configurables, validate_overrides},
+    ConfigurableValue, Error, Result,
+};
+
+const BLOB_ID_SIZE: u16 = 32;
+
+/// The first of the registers a loader uses unless [`LoaderBuilder::with_base_register`] says
+/// otherwise.
+pub const DEFAULT_BASE_REGISTER: u8 = 0x10;
+/// How many consecutive registers, starting from the base register, a loader uses.
+pub const LOADER_REGISTER_COUNT: u8 = 7;
+/// Registers Sway sets aside for its calling convention, from the top down: the data section
+/// pointer, return address, scratch, locals base, return value and six argument registers.
+const SWAY_RESERVED_REGISTERS: RangeInclusive<u8> = 0x35..=0x3F;
+
+/// The base registers a loader may be built with.
+///
+/// Everything below `0x10` is reserved by the VM. The loaded code expects nothing of the
+/// writable registers Sway allocates as it goes, so the loader may use any block of those, as
+/// long as all [`LOADER_REGISTER_COUNT`] end below the ones Sway reserves, from `0x35` on.
+pub const SAFE_BASE_REGISTERS: RangeInclusive<u8> =
+    0x10..=*SWAY_RESERVED_REGISTERS.start() - LOADER_REGISTER_COUNT;
+
+/// Largest value a `movi` immediate can hold.
+///
+/// Builder options the prologue bakes into a `movi`, such as
+/// [`LoaderBuilder::with_debug_log_region`] lengths, can't exceed it. Sizes that may be larger,
+/// like [`LoaderBuilder::with_expected_code_size`], are embedded as words after the blob id and
+/// read with an `lw` instead.
+pub const MAX_IMMEDIATE: u64 = (1 << 18) - 1;
+
+/// Largest value an `addi` immediate can hold, which bounds the length of a loader's prologue.
+const MAX_ADDI_IMMEDIATE: usize = (1 << 12) - 1;
+
+/// Revert code of a loader whose blob isn't the size given to
+/// [`LoaderBuilder::with_expected_code_size`].
+pub const CODE_SIZE_MISMATCH_REVERT_CODE: u32 = 0x1_0AD0;
+
+/// Revert code of a loader built [`LoaderBuilder::with_jump_guard`] whose jump target lies
+/// outside the loaded code.
+pub const JUMP_GUARD_REVERT_CODE: u32 = 0x1_0AD1;
+
+/// Id of the gas marker logged once the blob has been loaded.
+pub const GAS_MARKER_BLOB_LOADED: u32 = 1;
+/// Id of the gas marker logged once the data section has been loaded.
+pub const GAS_MARKER_DATA_SECTION_LOADED: u32 = 2;
+/// Id of the gas marker logged right before jumping into the loaded code.
+pub const GAS_MARKER_BEFORE_JUMP: u32 = 3;
+
+/// The registers a loader uses, allocated consecutively from its base register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Registers {
+    pub(crate) address_of_data_after_code: u8,
+    pub(crate) start_of_loaded_code: u8,
+    pub(crate) general_use: u8,
+    pub(crate) gas_marker: u8,
+    pub(crate) scratch: u8,
+    /// Size of the blob, kept for debug log regions relative to the data section.
+    pub(crate) code_len: u8,
+    /// Length of the debug log.
+    pub(crate) debug_log_len: u8,
+}
+
+impl Registers {
+    pub(crate) fn from_base(base: u8) -> Self {
+        Self {
+            address_of_data_after_code: base,
+            start_of_loaded_code: base + 1,
+            general_use: base + 2,
+            gas_marker: base + 3,
+            scratch: base + 4,
+            code_len: base + 5,
+            debug_log_len: base + 6,
+        }
+    }
+
+    /// Recovers the registers from a decoded prologue, whose first instruction moves `$pc` into
+    /// the base register.
+    pub(crate) fn of(prologue: &[Instruction]) -> Result<Self> {
+        match prologue.first() {
+            Some(Instruction::MOVE(locate)) => {
+                let (base, src) = locate.unpack();
+                if src != RegId::PC || !SAFE_BASE_REGISTERS.contains(&base.to_u8()) {
+                    return Err(Error::NotALoader);
+                }
+                Ok(Self::from_base(base.to_u8()))
+            }
+            _ => Err(Error::NotALoader),
+        }
+    }
+}
+
+pub fn extract_data_offset(binary: &[u8]) -> Result<usize> {
+    let data_offset: [u8; 8] = binary
+        .get(8..16)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::BinaryTooShort { len: binary.len() })?;
+
+    Ok(u64::from_be_bytes(data_offset) as usize)
+}
+
+/// Splits `binary` into its code, which goes into the blob, and its data section, which gets
+/// embedded in the loader.
+///
+/// Errors with [`Error::EmptyCode`] if the data offset is zero: there would be no code to put
+/// into the blob, and a loader jumping into nothing would run whatever follows on the stack.
+pub fn split_binary(binary: &[u8]) -> Result<(&[u8], &[u8])> {
+    let offset = extract_data_offset(binary)?;
+    if offset == 0 {
+        return Err(Error::EmptyCode);
+    }
+    if offset > binary.len() {
+        return Err(Error::InvalidDataOffset {
+            offset,
+            len: binary.len(),
+        });
+    }
+
+    Ok(binary.split_at(offset))
+}
+
+/// Builds a loader that logs the loaded code and data section before jumping into it, see
+/// [`LoaderBuilder::with_debug_log`].
+pub fn transform_into_configurable_loader(binary: &[u8], blob_id: &[u8; 32]) -> Result<Vec<u8>> {
+    LoaderBuilder::default()
+        .with_debug_log(true)
+        .build(binary, blob_id)
+}
+
+/// Length in bytes of `loader`'s instructions, up to and including the jump into the loaded code.
+/// The blob id starts right after them.
+pub fn prologue_byte_len(loader: &[u8]) -> Result<usize> {
+    Ok(decode_prologue(loader)?.len() * Instruction::SIZE)
+}
+
+/// Returns the id of the blob `loader` loads its code from.
+pub fn find_blob_id(loader: &[u8]) -> Result<[u8; 32]> {
+    let start = prologue_byte_len(loader)?;
+
+    loader
+        .get(start..start + BLOB_ID_SIZE as usize)
+        .map(|blob_id| blob_id.try_into().expect("slice is BLOB_ID_SIZE long"))
+        .ok_or(Error::NotALoader)
+}
+
+/// Returns the id of every blob `loader` loads, in the order its prologue loads them: the code
+/// blob, then the data section's blob if it was built with [`DataSectionStrategy::Blob`]. A
+/// loader with an embedded data section gives just what [`find_blob_id`] returns.
+pub fn multi_blob_ids(loader: &[u8]) -> Result<Vec<[u8; 32]>> {
+    let parsed = parse_loader(loader)?;
+
+    Ok([parsed.blob_id]
+        .into_iter()
+        .chain(parsed.data_blob_id)
+        .collect())
+}
+
+/// Whether `loader` logs the loaded code and data section on every run, see
+/// [`LoaderBuilder::with_debug_log`].
+pub fn has_debug_log(loader: &[u8]) -> Result<bool> {
+    let prologue = decode_prologue(loader)?;
+    let registers = Registers::of(&prologue)?;
+
+    Ok(prologue.contains(&debug_log_instruction(registers)))
+}
+
+/// Removes the debug log from a loader built [`LoaderBuilder::with_debug_log`]. Loaders without a
+/// debug log are returned unchanged.
+///
+/// The result is what building without the debug log gives, unless the loader was
+/// [`LoaderBuilder::optimize`]d: the debug log needs the absolute address of the loaded code, so
+/// its prologue snapshots `$sp` as is and subtracts `$is` before the jump. Stripping keeps that
+/// pair, leaving the prologue one instruction longer than an optimized build without the debug
+/// log. It still loads the same blob and data section and jumps to the same place.
+pub fn strip_debug_log(loader: &[u8]) -> Result<Vec<u8>> {
+    if !is_loader(loader) {
+        return Err(Error::NotALoader);
+    }
+    let prologue = decode_prologue(loader)?;
+    let registers = Registers::of(&prologue)?;
+    // The `addi` locating the blob id is rewritten below, it has to be where the builder put it.
+    let locates_blob_id = match prologue.get(1) {
+        Some(Instruction::ADDI(addi)) => {
+            let (dest, _, _) = addi.unpack();
+            dest.to_u8() == registers.address_of_data_after_code
+        }
+        _ => false,
+    };
+    if !locates_blob_id {
+        return Err(Error::NotALoader);
+    }
+
+    let debug_log_instructions = [
+        op::move_(registers.debug_log_len, registers.general_use),
+        op::add(
+            registers.debug_log_len,
+            registers.debug_log_len,
+            registers.general_use,
+        ),
+        debug_log_instruction(registers),
+    ];
+
+    let mut kept: Vec<_> = prologue
+        .iter()
+        .copied()
+        .filter(|instruction| !debug_log_instructions.contains(instruction))
+        .collect();
+
+    // The blob id now starts right after the shorter prologue.
+    let prologue_len = kept.len() * Instruction::SIZE;
+    if prologue_len > MAX_ADDI_IMMEDIATE {
+        return Err(Error::PrologueTooLong(prologue_len));
+    }
+    kept[1] = op::addi(
+        registers.address_of_data_after_code,
+        registers.address_of_data_after_code,
+        prologue_len as u16,
+    );
+
+    let rest = &loader[prologue.len() * Instruction::SIZE..];
+    Ok(kept
+        .into_iter()
+        .flat_map(|instruction| instruction.to_bytes())
+        .chain(rest.iter().copied())
+        .collect())
+}
+
+/// A loader known to parse, along with where its data section started in the original binary,
+/// which configurable offsets in the ABI are relative to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loader {
+    bytes: Vec<u8>,
+    data_offset: usize,
+}
+
+impl Loader {
+    /// Wraps `bytes`, erroring if they don't parse as a loader. `data_offset` is the data offset of
+    /// the binary the loader was built from.
+    pub fn new(bytes: Vec<u8>, data_offset: usize) -> Result<Self> {
+        parse_loader(&bytes)?;
+        Ok(Self { bytes, data_offset })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn data_offset(&self) -> usize {
+        self.data_offset
+    }
+
+    pub fn parsed(&self) -> ParsedLoader<'_> {
+        parse_loader(&self.bytes).expect("validated on construction")
+    }
+
+    /// A copy of this loader with `overrides` written into its data section. The instructions,
+    /// the blob id and everything else stay as they are.
+    ///
+    /// Errors with [`Error::DataSectionInBlob`] for a loader built with
+    /// [`DataSectionStrategy::Blob`]: its data section is in that blob, so a new data blob has to
+    /// be uploaded, and a new loader built for it, instead.
+    pub fn rebuild_with(
+        &self,
+        abi_json: &str,
+        overrides: &[(String, ConfigurableValue)],
+    ) -> Result<Self> {
+        let parsed = self.parsed();
+        if let Some(data_blob_id) = parsed.data_blob_id {
+            return Err(Error::DataSectionInBlob(data_blob_id));
+        }
+        let prologue = decode_prologue(&self.bytes)?;
+        let start = parsed.instructions.len()
+            + BLOB_ID_SIZE as usize
+            + expected_sizes_len(&prologue)
+            + WORD_SIZE;
+        let len = parsed.data_section.len();
+
+        let mut bytes = self.bytes.clone();
+        let data_section = &mut bytes[start..start + len];
+        validate_overrides(data_section, self.data_offset, abi_json, overrides)?;
+        apply_configurables(data_section, self.data_offset, abi_json, overrides)?;
+
+        Self::new(bytes, self.data_offset)
+    }
+}
+
+/// Builds the default loader for `binary` with `overrides` applied, without any provider.
+///
+/// `blob_id` must be the id of the blob holding `binary`'s code, e.g. from
+/// [`crate::compute_blob_id`]. The result is byte for byte what [`crate::deploy_loader`] builds
+/// for the same inputs, so it can be produced on an offline machine and deployed elsewhere.
+pub fn build_offline(
+    binary: &[u8],
+    blob_id: &[u8; 32],
+    overrides: &[(String, ConfigurableValue)],
+    abi_json: &str,
+) -> Result<Loader> {
+    let builder = overrides.iter().cloned().fold(
+        LoaderBuilder::default().with_abi(abi_json),
+        |builder, (name, value)| builder.set_configurable(name, value),
+    );
+
+    builder.build_loader(binary, blob_id)
+}
+
+/// The regions of a loader, see [`parse_loader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedLoader<'a> {
+    /// The loader instructions, up to and including the jump into the loaded code.
+    pub instructions: &'a [u8],
+    pub blob_id: [u8; 32],
+    /// The data section the loaded code runs against, empty if it is loaded from a blob.
+    pub data_section: &'a [u8],
+    /// Set if the loader was built with [`DataSectionStrategy::Blob`].
+    pub data_blob_id: Option<[u8; 32]>,
+    /// Set if the loader was built [`LoaderBuilder::with_metadata`].
+    pub metadata: Option<&'a str>,
+}
+
+/// The binary `loader` runs when its blob holds `code`: the code followed by the embedded data
+/// section. Errors like [`runtime_data_section`] for loaders built with
+/// [`DataSectionStrategy::Blob`].
+pub fn reconstruct_binary(code: &[u8], loader: &[u8]) -> Result<Vec<u8>> {
+    Ok([code, runtime_data_section(loader)?].concat())
+}
+
+/// Splits `loader` into its instructions, blob id, data section and trailing metadata.
+pub fn parse_loader(loader: &[u8]) -> Result<ParsedLoader<'_>> {
+    let prologue = decode_prologue(loader)?;
+    let (instructions, rest) = loader.split_at(prologue.len() * Instruction::SIZE);
+
+    let (blob_id, rest) = split_first_chunk::<{ BLOB_ID_SIZE as usize }>(rest)?;
+    let rest = rest
+        .get(expected_sizes_len(&prologue)..)
+        .ok_or(Error::NotALoader)?;
+    if loads_data_section_from_blob(&prologue) {
+        let (data_blob_id, tail) = split_first_chunk::<{ BLOB_ID_SIZE as usize }>(rest)?;
+        return Ok(ParsedLoader {
+            instructions,
+            blob_id,
+            data_section: &[],
+            data_blob_id: Some(data_blob_id),
+            metadata: parse_metadata(tail)?,
+        });
+    }
+    let (data_section_len, rest) = split_first_chunk::<WORD_SIZE>(rest)?;
+    let data_section_len = u64::from_be_bytes(data_section_len) as usize;
+
+    if rest.len() < data_section_len {
+        return Err(Error::NotALoader);
+    }
+    let (data_section, tail) = rest.split_at(data_section_len);
+
+    Ok(ParsedLoader {
+        instructions,
+        blob_id,
+        data_section,
+        data_blob_id: None,
+        metadata: parse_metadata(tail)?,
+    })
+}
+
+/// Bytes of expected sizes between the blob id and the data section. The prologue reads each with
+/// an `lw` before loading the blob, see [`LoaderBuilder::with_expected_code_size`].
+fn expected_sizes_len(prologue: &[Instruction]) -> usize {
+    let words = prologue
+        .iter()
+        .take_while(|instruction| !matches!(instruction, Instruction::LDC(_)))
+        .filter(|instruction| matches!(instruction, Instruction::LW(_)))
+        .count();
+
+    words * WORD_SIZE
+}
+
+/// A loader built with [`DataSectionStrategy::Blob`] issues a second mode 1 `ldc`, for the data
+/// section's blob.
+fn loads_data_section_from_blob(prologue: &[Instruction]) -> bool {
+    let blob_loads = prologue
+        .iter()
+        .filter(|instruction| {
+            matches!(instruction, Instruction::LDC(ldc) if ldc.unpack().3.to_u8() == 1)
+        })
+        .count();
+
+    blob_loads > 1
+}
+
+/// The bytes the loader's mode 2 `ldc` copies onto the stack right after the loaded code, i.e.
+/// the embedded data section the loaded code runs against, configurable overrides included.
+///
+/// Errors with [`Error::DataSectionInBlob`] if `loader` was built with
+/// [`DataSectionStrategy::Blob`], since its data section is in that blob and not in the loader.
+pub fn runtime_data_section(loader: &[u8]) -> Result<&[u8]> {
+    let parsed = parse_loader(loader)?;
+    if let Some(data_blob_id) = parsed.data_blob_id {
+        return Err(Error::DataSectionInBlob(data_blob_id));
+    }
+
+    Ok(parsed.data_section)
+}
+
+fn split_first_chunk<const N: usize>(bytes: &[u8]) -> Result<([u8; N], &[u8])> {
+    if bytes.len() < N {
+        return Err(Error::NotALoader);
+    }
+    let (chunk, rest) = bytes.split_at(N);
+
+    Ok((chunk.try_into().expect("chunk is N long"), rest))
+}
+
+/// Metadata is stored after the data section as a length word followed by UTF-8 bytes. The
+/// loader never reads past the data section, so the loaded code can't observe it.
+fn encode_metadata(metadata: &str) -> Vec<u8> {
+    let len = metadata.len() as u64;
+
+    len.to_be_bytes()
+        .into_iter()
+        .chain(metadata.bytes())
+        .collect()
+}
+
+/// Anything after the metadata is padding, see [`LoaderBuilder::with_pad_to`]. An empty record
+/// only exists to introduce padding, so it reads as no metadata.
+fn parse_metadata(tail: &[u8]) -> Result<Option<&str>> {
+    if tail.is_empty() {
+        return Ok(None);
+    }
+
+    let (len, rest) = split_first_chunk::<WORD_SIZE>(tail).map_err(|_| Error::InvalidMetadata)?;
+    let metadata = rest
+        .get(..u64::from_be_bytes(len) as usize)
+        .ok_or(Error::InvalidMetadata)?;
+
+    std::str::from_utf8(metadata)
+        .map(|metadata| Some(metadata).filter(|metadata| !metadata.is_empty()))
+        .map_err(|_| Error::InvalidMetadata)
+}
+
+/// Whether `bytes` start like a loader generated by [`LoaderBuilder`] does.
+pub fn is_loader(bytes: &[u8]) -> bool {
+    starts_with_loader_prologue(bytes) && parse_loader(bytes).is_ok()
+}
+
+/// Whether the data section embedded in `loader` itself starts with a loader, which happens when
+/// a loader is mistakenly wrapped a second time.
+pub fn contains_nested_loader(loader: &[u8]) -> Result<bool> {
+    Ok(starts_with_loader_prologue(
+        parse_loader(loader)?.data_section,
+    ))
+}
+
+/// Every loader starts by locating the data after its code and snapshotting `$sp`. The `addi`
+/// in between depends on the number of instructions, so only its registers are compared.
+fn starts_with_loader_prologue(bytes: &[u8]) -> bool {
+    let instructions: Vec<_> = bytes
+        .chunks_exact(Instruction::SIZE)
+        .take(3)
+        .map(|chunk| {
+            let bytes: [u8; Instruction::SIZE] = chunk.try_into().expect("chunks are exact");
+            Instruction::try_from(bytes).ok()
+        })
+        .collect();
+
+    let &[Some(locate), Some(Instruction::ADDI(addi)), Some(snapshot)] = instructions.as_slice()
+    else {
+        return false;
+    };
+    let (dest, src, _) = addi.unpack();
+
+    let Ok(registers) = Registers::of(&[locate]) else {
+        return false;
+    };
+
+    // Optimized loaders snapshot `$sp` relative to `$is`, see `LoaderBuilder::optimize`.
+    let snapshots = [
+        op::move_(registers.start_of_loaded_code, RegId::SP),
+        op::sub(registers.start_of_loaded_code, RegId::SP, RegId::IS),
+    ];
+
+    dest.to_u8() == registers.address_of_data_after_code
+        && src.to_u8() == registers.address_of_data_after_code
+        && snapshots.contains(&snapshot)
+}
+
+/// Decodes the loader instructions up to and including the jump into the loaded code.
+pub(crate) fn decode_prologue(loader: &[u8]) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![];
+    for chunk in loader.chunks_exact(Instruction::SIZE) {
+        let bytes: [u8; Instruction::SIZE] = chunk.try_into().expect("chunks are exact");
+        let instruction = Instruction::try_from(bytes).map_err(|_| Error::NotALoader)?;
+        instructions.push(instruction);
+
+        if matches!(instruction, Instruction::JMP(_)) {
+            return Ok(instructions);
+        }
+    }
+
+    Err(Error::NotALoader)
+}
+
+pub(crate) fn debug_log_instruction(registers: Registers) -> Instruction {
+    op::logd(
+        RegId::ZERO,
+        RegId::ZERO,
+        registers.start_of_loaded_code,
+        registers.debug_log_len,
+    )
+}
+
+/// Where a region logged by [`LoaderBuilder::with_debug_log_region`] starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugLogBase {
+    /// The start of the loaded code.
+    Code,
+    /// The start of the data section, right after the loaded code.
+    DataSection,
+}
+
+/// Where the loader gets the data section it copies after the loaded code from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataSectionStrategy {
+    /// Embedded in the loader itself.
+    #[default]
+    Embedded,
+    /// Loaded from the blob with the given id, which must hold exactly
+    /// [`LoaderBuilder::configured_data_section`]. Keeps the loader at a fixed small size no
+    /// matter how large the data section is, at the cost of a second blob load on every run.
+    Blob([u8; 32]),
+}
+
+/// The execution context a loader runs in.
+///
+/// Every target ends with the same jump into the loaded code. The target only decides which
+/// builder options are available.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoaderTarget {
+    #[default]
+    Script,
+    /// Predicates can't emit receipts, so neither the debug log nor gas markers are available.
+    Predicate,
+    Contract,
+}
+
+/// Turns a compiled binary into a loader that fetches the code from a blob and runs it with the
+/// data section embedded in the loader.
+#[derive(Debug, Clone, Default)]
+pub struct LoaderBuilder {
+    debug_log: bool,
+    gas_markers: bool,
+    expected_code_size: Option<u64>,
+    expected_blob_size: Option<u64>,
+    metadata: Option<String>,
+    strict: bool,
+    target: LoaderTarget,
+    abi: Option<String>,
+    configurables: Vec<(String, ConfigurableValue)>,
+    base_register: Option<u8>,
+    pad_to: Option<usize>,
+    debug_log_regions: Vec<(DebugLogBase, u32)>,
+    max_copy_size: Option<u32>,
+    blob_id_log: bool,
+    jump_guard: bool,
+    entry_offset: u32,
+    data_section_strategy: DataSectionStrategy,
+    optimize: bool,
+}
+
+impl LoaderBuilder {
+    /// The program's ABI JSON, needed to locate configurables in the data section.
+    pub fn with_abi(mut self, abi_json: impl Into<String>) -> Self {
+        self.abi = Some(abi_json.into());
+        self
+    }
+
+    /// Overrides the compiled-in value of the configurable `name` in the embedded data section.
+    pub fn set_configurable(mut self, name: impl Into<String>, value: ConfigurableValue) -> Self {
+        self.configurables.push((name.into(), value));
+        self
+    }
+
+    pub(crate) fn configurable_overrides(&self) -> &[(String, ConfigurableValue)] {
+        &self.configurables
+    }
+
+    pub(crate) fn debug_log_enabled(&self) -> bool {
+        self.debug_log
+    }
+
+    pub(crate) fn gas_markers_enabled(&self) -> bool {
+        self.gas_markers
+    }
+
+    pub(crate) fn blob_id_log_enabled(&self) -> bool {
+        self.blob_id_log
+    }
+
+    pub(crate) fn debug_log_region_count(&self) -> usize {
+        self.debug_log_regions.len()
+    }
+
+    /// Logs the loaded code followed by the data section right before jumping into it, so the
+    /// receipts show exactly what the loaded code runs against. Costs a `logd` of the whole
+    /// program on every run, so leave it off outside of debugging.
+    pub fn with_debug_log(mut self, enabled: bool) -> Self {
+        self.debug_log = enabled;
+        self
+    }
+
+    /// Logs the `len` bytes starting at `base` right before jumping into the loaded code, e.g.
+    /// only the data section instead of the whole program [`LoaderBuilder::with_debug_log`]
+    /// logs. Each call adds another `logd`, emitted after the full debug log if that is on.
+    ///
+    /// `len` can't exceed [`MAX_IMMEDIATE`].
+    pub fn with_debug_log_region(mut self, base: DebugLogBase, len: u32) -> Self {
+        self.debug_log_regions.push((base, len));
+        self
+    }
+
+    /// Copies the data section onto the stack in chunks of at most `size` bytes instead of with a
+    /// single `ldc`, for data sections too large to copy at once. The chunks are laid out back to
+    /// back, so `size` must be a multiple of the word size, and it can't exceed
+    /// [`MAX_IMMEDIATE`].
+    ///
+    /// The chunks are copied in a loop of a fixed ten instructions, and each full chunk runs its
+    /// `ldc`, an `add`, a `subi` and a `jnzb` once.
+    pub fn with_max_copy_size(mut self, size: u32) -> Self {
+        self.max_copy_size = Some(size);
+        self
+    }
+
+    /// Logs the 32-byte id of the blob right after loading it, so every run's receipts record
+    /// which blob it executed at a fraction of the cost of [`LoaderBuilder::with_debug_log`].
+    pub fn with_blob_id_log(mut self, enabled: bool) -> Self {
+        self.blob_id_log = enabled;
+        self
+    }
+
+    /// Reverts with [`JUMP_GUARD_REVERT_CODE`] instead of jumping when the jump target lies
+    /// outside the loaded code, turning an opaque VM panic into a revert that says what went
+    /// wrong.
+    ///
+    /// Adds an `add`, an `lt` and a `jnzf` to every run, plus a `movi` and `rvrt` that only
+    /// execute when the target is out of bounds.
+    pub fn with_jump_guard(mut self, enabled: bool) -> Self {
+        self.jump_guard = enabled;
+        self
+    }
+
+    /// Enters the loaded code `offset` bytes past its start instead of at its first instruction.
+    /// `offset` must be a multiple of the instruction size and can't exceed [`MAX_IMMEDIATE`].
+    pub fn with_entry_offset(mut self, offset: u32) -> Self {
+        self.entry_offset = offset;
+        self
+    }
+
+    /// Emits a `log` at each phase boundary: after the blob is loaded, after the data section is
+    /// loaded and right before the jump. Each receipt carries the marker id in `ra` and the
+    /// remaining global gas in `rb`, so the difference between consecutive markers is the gas
+    /// spent in that phase.
+    pub fn with_gas_markers(mut self, enabled: bool) -> Self {
+        self.gas_markers = enabled;
+        self
+    }
+
+    /// Reverts with [`CODE_SIZE_MISMATCH_REVERT_CODE`] unless the blob is exactly `size` bytes,
+    /// catching a truncated or otherwise wrong blob before its code runs. `size` is embedded as a
+    /// word after the blob id.
+    ///
+    /// The check adds an `lw`, an `eq` and a `jnzf` to every run, plus a `movi` and `rvrt` that
+    /// only execute on a mismatch.
+    pub fn with_expected_code_size(mut self, size: u64) -> Self {
+        self.expected_code_size = Some(size);
+        self
+    }
+
+    /// Loads exactly `size` bytes of the blob instead of however many `bsiz` reports, so a blob
+    /// padded past the end of the code can't shift what follows it. The data section then lands
+    /// right after those `size` bytes. `size` is embedded as a word after the blob id, following
+    /// the one of [`LoaderBuilder::with_expected_code_size`] if both are set.
+    ///
+    /// Replaces the `bsiz` unless [`LoaderBuilder::with_expected_code_size`] needs it for its
+    /// check.
+    pub fn with_expected_blob_size(mut self, size: u64) -> Self {
+        self.expected_blob_size = Some(size);
+        self
+    }
+
+    /// Appends `metadata`, e.g. the build commit or source path, after the data section so a
+    /// deployed loader can be traced back to its build. It is never loaded, so it doesn't affect
+    /// execution. Read it back with [`parse_loader`].
+    pub fn with_metadata(mut self, metadata: &str) -> Self {
+        self.metadata = Some(metadata.to_string());
+        self
+    }
+
+    /// Refuses to build, with [`Error::NestedLoader`], when `binary` or its data section already
+    /// looks like a loader. Without it such inputs are wrapped as-is.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Pads the loader with trailing zeroes to exactly `size` bytes, for tooling with fixed-size
+    /// bytecode slots. The padding follows the data section and metadata, where neither the
+    /// loader nor the loaded code ever reads.
+    ///
+    /// Without [`LoaderBuilder::with_metadata`] the padding starts with an empty metadata record
+    /// so the loader still parses, which takes 8 bytes. Errors with [`Error::PaddingTooSmall`]
+    /// if the loader doesn't fit.
+    pub fn with_pad_to(mut self, size: usize) -> Self {
+        self.pad_to = Some(size);
+        self
+    }
+
+    /// Where the loader gets the data section from, [`DataSectionStrategy::Embedded`] by default.
+    ///
+    /// With [`DataSectionStrategy::Blob`] the data section is copied with a mode 1 `ldc` instead,
+    /// so [`LoaderBuilder::with_max_copy_size`] has no effect. Configurables are baked into the
+    /// blob, set them before computing its content with
+    /// [`LoaderBuilder::configured_data_section`].
+    pub fn with_data_section_strategy(mut self, strategy: DataSectionStrategy) -> Self {
+        self.data_section_strategy = strategy;
+        self
+    }
+
+    /// Emits the prologue with the fewest instructions that behave the same: the data section
+    /// length is read with an offset instead of stepping over the blob id first, and unless a
+    /// `logd` needs the loaded code's absolute address, `$sp` is snapshotted relative to `$is`
+    /// so the jump needs no `sub`.
+    pub fn optimize(mut self, enabled: bool) -> Self {
+        self.optimize = enabled;
+        self
+    }
+
+    /// Makes the loader use the [`LOADER_REGISTER_COUNT`] registers starting at `base` instead of
+    /// [`DEFAULT_BASE_REGISTER`]. `base` must be in [`SAFE_BASE_REGISTERS`].
+    pub fn with_base_register(mut self, base: u8) -> Self {
+        self.base_register = Some(base);
+        self
+    }
+
+    /// The sizes embedded after the blob id, in the order the prologue reads them.
+    fn expected_sizes(&self) -> Vec<u64> {
+        self.expected_code_size
+            .into_iter()
+            .chain(self.expected_blob_size)
+            .collect()
+    }
+
+    /// Bytes between the blob id and the data section, holding [`LoaderBuilder::expected_sizes`].
+    fn expected_sizes_len(&self) -> u16 {
+        (self.expected_sizes().len() * WORD_SIZE) as u16
+    }
+
+    pub(crate) fn registers(&self) -> Registers {
+        Registers::from_base(self.base_register.unwrap_or(DEFAULT_BASE_REGISTER))
+    }
+
+    /// The context the loader will run in, [`LoaderTarget::Script`] by default.
+    pub fn with_target(mut self, target: LoaderTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn build(&self, binary: &[u8], blob_id: &[u8; 32]) -> Result<Vec<u8>> {
+        // The final code is going to have this structure (if the data section is non-empty):
+        // 1. loader instructions
+        // 2. blob id
+        // 3. expected code and blob sizes, if any
+        // 4. length_of_data_section
+        // 5. the data_section (updated with configurables as needed)
+        // 6. metadata, if any
+        // With `DataSectionStrategy::Blob`, 4. and 5. are replaced by the data section's blob id.
+        if let Some(base) = self
+            .base_register
+            .filter(|base| !SAFE_BASE_REGISTERS.contains(base))
+        {
+            return Err(Error::UnsafeBaseRegister(base));
+        }
+        if let Some((_, len)) = self
+            .debug_log_regions
+            .iter()
+            .find(|(_, len)| u64::from(*len) > MAX_IMMEDIATE)
+        {
+            return Err(Error::DebugLogRegionTooLarge(*len));
+        }
+        if let Some(size) = self.max_copy_size.filter(|size| {
+            *size == 0 || *size as usize % WORD_SIZE != 0 || u64::from(*size) > MAX_IMMEDIATE
+        }) {
+            return Err(Error::InvalidMaxCopySize(size));
+        }
+        if self.entry_offset as usize % Instruction::SIZE != 0
+            || u64::from(self.entry_offset) > MAX_IMMEDIATE
+        {
+            return Err(Error::InvalidEntryOffset(self.entry_offset));
+        }
+        if self.target == LoaderTarget::Predicate {
+            let receipts = [
+                (self.debug_log, "the debug log"),
+                (self.blob_id_log, "the blob id log"),
+                (!self.debug_log_regions.is_empty(), "debug log regions"),
+                (self.gas_markers, "gas markers"),
+            ];
+            if let Some((_, feature)) = receipts.into_iter().find(|(enabled, _)| *enabled) {
+                return Err(Error::UnsupportedByTarget {
+                    target: self.target,
+                    feature,
+                });
+            }
+        }
+
+        let data_section = self.configured_data_section(binary)?;
+
+        // The blob id is located with an `addi` past the instructions, see `load_blob_sequence`.
+        let prologue_len = self.instructions(0).len() * Instruction::SIZE;
+        if prologue_len > MAX_ADDI_IMMEDIATE {
+            return Err(Error::PrologueTooLong(prologue_len));
+        }
+        let num_of_instructions = (prologue_len / Instruction::SIZE) as u16;
+
+        let instruction_bytes = self
+            .instructions(num_of_instructions)
+            .into_iter()
+            .flat_map(|instruction| instruction.to_bytes());
+
+        let blob_bytes = blob_id.iter().copied();
+
+        let size_bytes = self.expected_sizes().into_iter().flat_map(u64::to_be_bytes);
+
+        let data_section_bytes = match self.data_section_strategy {
+            DataSectionStrategy::Embedded => {
+                let data_section_len: u64 = u64::try_from(data_section.len())
+                    .expect("to never have more than u64::MAX data section length");
+                [data_section_len.to_be_bytes().to_vec(), data_section].concat()
+            }
+            DataSectionStrategy::Blob(data_blob_id) => data_blob_id.to_vec(),
+        };
+
+        let metadata = self
+            .metadata
+            .as_deref()
+            .or(self.pad_to.map(|_| ""))
+            .map(encode_metadata);
+
+        let mut loader: Vec<_> = instruction_bytes
+            .chain(blob_bytes)
+            .chain(size_bytes)
+            .chain(data_section_bytes)
+            .chain(metadata.into_iter().flatten())
+            .collect();
+
+        if let Some(pad_to) = self.pad_to {
+            if loader.len() > pad_to {
+                return Err(Error::PaddingTooSmall {
+                    len: loader.len(),
+                    pad_to,
+                });
+            }
+            loader.resize(pad_to, 0);
+        }
+
+        Ok(loader)
+    }
+
+    /// The data section of `binary` with the configurable overrides applied, i.e. what the
+    /// loaded code runs against. This is the content of the blob a loader built with
+    /// [`DataSectionStrategy::Blob`] loads.
+    pub fn configured_data_section(&self, binary: &[u8]) -> Result<Vec<u8>> {
+        let (code, data_section) = split_binary(binary)?;
+        let offset = code.len();
+        if self.strict && (is_loader(binary) || starts_with_loader_prologue(data_section)) {
+            return Err(Error::NestedLoader);
+        }
+        let mut data_section = data_section.to_vec();
+        if !self.configurables.is_empty() {
+            let abi = self.abi.as_deref().ok_or(Error::MissingAbi)?;
+            validate_overrides(&data_section, offset, abi, &self.configurables)?;
+            apply_configurables(&mut data_section, offset, abi, &self.configurables)?;
+        }
+
+        Ok(data_section)
+    }
+
+    /// Like [`LoaderBuilder::build`], keeping what [`Loader::rebuild_with`] needs to patch the
+    /// result later.
+    pub fn build_loader(&self, binary: &[u8], blob_id: &[u8; 32]) -> Result<Loader> {
+        let data_offset = extract_data_offset(binary)?;
+        Loader::new(self.build(binary, blob_id)?, data_offset)
+    }
+
+    fn instructions(&self, num_of_instructions: u16) -> Vec<Instruction> {
+        let registers = self.registers();
+        // There are 3 main steps:
+        // 1. Load the blob content into memory
+        // 2. Load the data section right after the blob
+        // 3. Jump to the beginning of the memory where the blob was loaded
+        let mut instructions = self.load_blob_sequence(num_of_instructions);
+        self.push_gas_marker(&mut instructions, GAS_MARKER_BLOB_LOADED);
+
+        if self.blob_id_log {
+            // address_of_data_after_code still points at the blob id.
+            instructions.extend([
+                op::movi(registers.scratch, u32::from(BLOB_ID_SIZE)),
+                op::logd(
+                    RegId::ZERO,
+                    RegId::ZERO,
+                    registers.address_of_data_after_code,
+                    registers.scratch,
+                ),
+            ]);
+        }
+
+        instructions.extend(self.load_data_section_sequence());
+        self.push_gas_marker(&mut instructions, GAS_MARKER_DATA_SECTION_LOADED);
+
+        if self.debug_log {
+            instructions.extend([
+                op::add(
+                    registers.debug_log_len,
+                    registers.debug_log_len,
+                    registers.general_use,
+                ),
+                debug_log_instruction(registers),
+            ]);
+        }
+        // The data section length in general_use isn't needed past this point.
+        for (base, len) in &self.debug_log_regions {
+            let start = match base {
+                DebugLogBase::Code => registers.start_of_loaded_code,
+                DebugLogBase::DataSection => {
+                    instructions.push(op::add(
+                        registers.scratch,
+                        registers.start_of_loaded_code,
+                        registers.code_len,
+                    ));
+                    registers.scratch
+                }
+            };
+            instructions.extend([
+                op::movi(registers.general_use, *len),
+                op::logd(RegId::ZERO, RegId::ZERO, start, registers.general_use),
+            ]);
+        }
+        self.push_gas_marker(&mut instructions, GAS_MARKER_BEFORE_JUMP);
+
+        instructions.extend(self.terminal_sequence());
+
+        instructions
+    }
+
+    fn load_blob_sequence(&self, num_of_instructions: u16) -> Vec<Instruction> {
+        let registers = self.registers();
+        let mut instructions = vec![
+            // 1. Load the blob content into memory
+            // Find the start of the hardcoded blob ID, which is located after the loader code ends.
+            op::move_(registers.address_of_data_after_code, RegId::PC),
+            // hold the address of the blob ID.
+            op::addi(
+                registers.address_of_data_after_code,
+                registers.address_of_data_after_code,
+                num_of_instructions * Instruction::SIZE as u16,
+            ),
+            // The code is going to be loaded from the current value of SP onwards, save
+            // the location into the start_of_loaded_code register so we can jump into it at the end.
+            if self.snapshots_relative_start() {
+                op::sub(registers.start_of_loaded_code, RegId::SP, RegId::IS)
+            } else {
+                op::move_(registers.start_of_loaded_code, RegId::SP)
+            },
+        ];
+        if self.expected_blob_size.is_none() || self.expected_code_size.is_some() {
+            // general_use to hold the size of the blob.
+            instructions.push(op::bsiz(
+                registers.general_use,
+                registers.address_of_data_after_code,
+            ));
+        }
+        // The expected sizes are the words right after the blob id.
+        let first_size_word = BLOB_ID_SIZE / WORD_SIZE as u16;
+        if self.expected_code_size.is_some() {
+            instructions.extend([
+                op::lw(
+                    registers.scratch,
+                    registers.address_of_data_after_code,
+                    first_size_word,
+                ),
+                op::eq(registers.scratch, registers.scratch, registers.general_use),
+                // Skip the revert if the sizes match.
+                op::jnzf(registers.scratch, RegId::ZERO, 2),
+                op::movi(registers.scratch, CODE_SIZE_MISMATCH_REVERT_CODE),
+                op::rvrt(registers.scratch),
+            ]);
+        }
+        if self.expected_blob_size.is_some() {
+            // Only load the intended bytes, whatever the blob holds past them.
+            instructions.push(op::lw(
+                registers.general_use,
+                registers.address_of_data_after_code,
+                first_size_word + u16::from(self.expected_code_size.is_some()),
+            ));
+        }
+        if self.debug_log {
+            instructions.push(op::move_(registers.debug_log_len, registers.general_use));
+        }
+        let data_section_region = self
+            .debug_log_regions
+            .iter()
+            .any(|(base, _)| *base == DebugLogBase::DataSection);
+        if data_section_region || self.jump_guard {
+            instructions.push(op::move_(registers.code_len, registers.general_use));
+        }
+        // Push the blob contents onto the stack.
+        instructions.push(op::ldc(
+            registers.address_of_data_after_code,
+            0,
+            registers.general_use,
+            1,
+        ));
+
+        instructions
+    }
+
+    pub(crate) fn load_data_section_sequence(&self) -> Vec<Instruction> {
+        let registers = self.registers();
+        if let DataSectionStrategy::Blob(_) = self.data_section_strategy {
+            return vec![
+                // 2. Load the data section blob right after the code blob
+                // Move on to the data section's blob id
+                op::addi(
+                    registers.address_of_data_after_code,
+                    registers.address_of_data_after_code,
+                    BLOB_ID_SIZE + self.expected_sizes_len(),
+                ),
+                // general_use to hold the size of the data section, as in the embedded case
+                op::bsiz(registers.general_use, registers.address_of_data_after_code),
+                op::ldc(
+                    registers.address_of_data_after_code,
+                    0,
+                    registers.general_use,
+                    1,
+                ),
+            ];
+        }
+
+        let mut instructions = if self.optimize {
+            vec![
+                // 2. Load the data section right after the blob
+                // load the size of the data section, the word after the blob id and the expected
+                // sizes, into general_use
+                op::lw(
+                    registers.general_use,
+                    registers.address_of_data_after_code,
+                    (BLOB_ID_SIZE + self.expected_sizes_len()) / WORD_SIZE as u16,
+                ),
+                // skip the blob id, the expected sizes and the length to get to the actual data
+                op::addi(
+                    registers.address_of_data_after_code,
+                    registers.address_of_data_after_code,
+                    BLOB_ID_SIZE + self.expected_sizes_len() + WORD_SIZE as u16,
+                ),
+            ]
+        } else {
+            vec![
+                // 2. Load the data section right after the blob
+                // Move on to the data section length
+                op::addi(
+                    registers.address_of_data_after_code,
+                    registers.address_of_data_after_code,
+                    BLOB_ID_SIZE + self.expected_sizes_len(),
+                ),
+                // load the size of the data section into general_use
+                op::lw(
+                    registers.general_use,
+                    registers.address_of_data_after_code,
+                    0,
+                ),
+                // after we have read the length of the data section, we move the pointer to the
+                // actual data by skipping WORD_SIZE B.
+                op::addi(
+                    registers.address_of_data_after_code,
+                    registers.address_of_data_after_code,
+                    WORD_SIZE as u16,
+                ),
+            ]
+        };
+
+        let Some(max_copy_size) = self.max_copy_size else {
+            // load the data section
+            instructions.push(op::ldc(
+                registers.address_of_data_after_code,
+                0,
+                registers.general_use,
+                2,
+            ));
+            return instructions;
+        };
+
+        // Copy the data section chunk by chunk in a loop, so the prologue stays the same length no
+        // matter how many chunks there are. Every full chunk is a whole number of words, so each
+        // lands right after the previous one. general_use keeps the full length for the debug log
+        // and gas_marker, free until the next marker, counts down the full chunks.
+        instructions.extend([
+            op::movi(registers.scratch, max_copy_size),
+            op::div(
+                registers.gas_marker,
+                registers.general_use,
+                registers.scratch,
+            ),
+            // Skip the jump over the loop if there is at least one full chunk.
+            op::jnzf(registers.gas_marker, RegId::ZERO, 1),
+            op::jmpf(RegId::ZERO, 4),
+            op::ldc(
+                registers.address_of_data_after_code,
+                0,
+                registers.scratch,
+                2,
+            ),
+            op::add(
+                registers.address_of_data_after_code,
+                registers.address_of_data_after_code,
+                registers.scratch,
+            ),
+            op::subi(registers.gas_marker, registers.gas_marker, 1),
+            // Back to the `ldc` while full chunks remain.
+            op::jnzb(registers.gas_marker, RegId::ZERO, 2),
+            // Then whatever is left over, possibly nothing.
+            op::mod_(
+                registers.gas_marker,
+                registers.general_use,
+                registers.scratch,
+            ),
+            op::ldc(
+                registers.address_of_data_after_code,
+                0,
+                registers.gas_marker,
+                2,
+            ),
+        ]);
+
+        instructions
+    }
+
+    /// The jump into the loaded code. `jmp` is relative to `$is`, which points at the code being
+    /// executed in scripts, predicates and contract calls alike, so this one sequence serves every
+    /// [`LoaderTarget`].
+    fn terminal_sequence(&self) -> Vec<Instruction> {
+        let registers = self.registers();
+        let mut instructions = vec![];
+        if self.jump_guard {
+            // general_use marks the end of the loaded code.
+            instructions.push(op::add(
+                registers.general_use,
+                registers.start_of_loaded_code,
+                registers.code_len,
+            ));
+        }
+        if self.entry_offset != 0 {
+            instructions.extend([
+                op::movi(registers.scratch, self.entry_offset),
+                op::add(
+                    registers.start_of_loaded_code,
+                    registers.start_of_loaded_code,
+                    registers.scratch,
+                ),
+            ]);
+        }
+        if self.jump_guard {
+            instructions.extend([
+                op::lt(
+                    registers.scratch,
+                    registers.start_of_loaded_code,
+                    registers.general_use,
+                ),
+                // Skip the revert if the target is inside the loaded code.
+                op::jnzf(registers.scratch, RegId::ZERO, 2),
+                op::movi(registers.scratch, JUMP_GUARD_REVERT_CODE),
+                op::rvrt(registers.scratch),
+            ]);
+        }
+
+        // 3. Jump to the beginning of the memory where the blob was loaded
+        // What follows is called _jmp_mem by the sway compiler.
+        if !self.snapshots_relative_start() {
+            // Subtract the address contained in IS because jmp will add it back.
+            instructions.push(op::sub(
+                registers.start_of_loaded_code,
+                registers.start_of_loaded_code,
+                RegId::IS,
+            ));
+        }
+        instructions.extend([
+            // jmp will multiply by 4, so we need to divide to cancel that out.
+            op::divi(
+                registers.start_of_loaded_code,
+                registers.start_of_loaded_code,
+                4,
+            ),
+            // Jump to the start of the code we loaded.
+            op::jmp(registers.start_of_loaded_code),
+        ]);
+
+        instructions
+    }
+
+    /// Whether the `$sp` snapshot is taken relative to `$is` right away, saving the `sub` before
+    /// the jump. Only [`LoaderBuilder::optimize`] does so, and only when no `logd` needs the
+    /// absolute address of the loaded code.
+    fn snapshots_relative_start(&self) -> bool {
+        self.optimize && !self.debug_log && self.debug_log_regions.is_empty()
+    }
+
+    fn push_gas_marker(&self, instructions: &mut Vec<Instruction>, id: u32) {
+        let registers = self.registers();
+        if self.gas_markers {
+            instructions.extend([
+                op::movi(registers.gas_marker, id),
+                op::log(registers.gas_marker, RegId::GGAS, RegId::ZERO, RegId::ZERO),
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        simulate::Vm,
+        test_fixtures::{binary_with_data_section, fixture_abi, fixture_binary},
+    };
+
+    const SIMULATED_BLOB_ID: [u8; 32] = [0xb1; 32];
+
+    // Regression test for the failure this repository was created to reproduce.
+    //
+    // The data section used to be copied with `ldc(REG_START_OF_DATA_SECTION, 0, len, 2)`, but the
+    // loader never wrote `REG_START_OF_DATA_SECTION`. The register kept its initial value of zero,
+    // so the mode 2 `ldc` copied `len` bytes from the very start of VM memory instead of the data
+    // section embedded after the blob id. The jump into the loaded code still succeeded, but the
+    // code ran against a garbage data section: configurables decoded to nonsense and the logged
+    // memory no longer matched the original binary.
+    //
+    // Walk the loader instructions up to the `jmp` and check that every register the loader owns
+    // is written before it is read.
+    #[test]
+    fn loader_does_not_read_uninitialized_registers() {
+        let binary = fixture_binary();
+
+        for builder in [
+            LoaderBuilder::default(),
+            LoaderBuilder::default().with_debug_log(true),
+            LoaderBuilder::default().with_gas_markers(true),
+            LoaderBuilder::default().with_expected_code_size(16),
+            LoaderBuilder::default().with_expected_blob_size(16),
+            LoaderBuilder::default().with_max_copy_size(16),
+        ] {
+            let loader = builder.build(&binary, &[0; 32]).unwrap();
+
+            let mut initialized = HashSet::new();
+            for instruction in decode_prologue(&loader).unwrap() {
+                // Only these write to their first register, every other operand is read.
+                let writes_first = matches!(
+                    instruction,
+                    Instruction::MOVE(_)
+                        | Instruction::MOVI(_)
+                        | Instruction::EQ(_)
+                        | Instruction::LT(_)
+                        | Instruction::ADD(_)
+                        | Instruction::ADDI(_)
+                        | Instruction::SUB(_)
+                        | Instruction::SUBI(_)
+                        | Instruction::DIV(_)
+                        | Instruction::DIVI(_)
+                        | Instruction::MOD(_)
+                        | Instruction::BSIZ(_)
+                        | Instruction::LW(_)
+                );
+
+                let [first, rest @ ..] = instruction.reg_ids();
+                let reads = rest.into_iter().chain((!writes_first).then_some(first));
+                for reg in reads.flatten() {
+                    let owned_by_loader = reg.to_u8() >= RegId::WRITABLE.to_u8();
+                    assert!(
+                        !owned_by_loader || initialized.contains(&reg.to_u8()),
+                        "{instruction:?} reads {reg:?} before the loader writes it"
+                    );
+                }
+
+                if writes_first {
+                    initialized.insert(first.expect("instruction writes a register").to_u8());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gas_markers_are_opt_in() {
+        let binary = fixture_binary();
+
+        let count_logs = |builder: LoaderBuilder| {
+            let loader = builder.build(&binary, &[0; 32]).unwrap();
+            decode_prologue(&loader)
+                .unwrap()
+                .into_iter()
+                .filter(|instruction| matches!(instruction, Instruction::LOG(_)))
+                .count()
+        };
+
+        assert_eq!(count_logs(LoaderBuilder::default()), 0);
+        assert_eq!(
+            count_logs(LoaderBuilder::default().with_gas_markers(true)),
+            3
+        );
+    }
+
+    #[test]
+    fn finds_the_blob_id() {
+        let binary = fixture_binary();
+        let blob_id = [7; 32];
+
+        for builder in [
+            LoaderBuilder::default(),
+            LoaderBuilder::default().with_debug_log(true),
+        ] {
+            let loader = builder.build(&binary, &blob_id).unwrap();
+
+            assert_eq!(find_blob_id(&loader).unwrap(), blob_id);
+        }
+    }
+
+    #[test]
+    fn detects_the_debug_log() {
+        let binary = fixture_binary();
+
+        let plain = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+        let debug = LoaderBuilder::default()
+            .with_debug_log(true)
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        assert!(!has_debug_log(&plain).unwrap());
+        assert!(has_debug_log(&debug).unwrap());
+    }
+
+    #[test]
+    fn expected_sizes_are_not_limited_to_an_immediate() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let large = MAX_IMMEDIATE + 1;
+
+        for builder in [
+            LoaderBuilder::default().with_expected_code_size(large),
+            LoaderBuilder::default().with_expected_blob_size(large),
+            LoaderBuilder::default()
+                .with_expected_code_size(large)
+                .with_expected_blob_size(large + 8),
+            LoaderBuilder::default()
+                .with_expected_blob_size(large)
+                .optimize(true),
+        ] {
+            let loader = builder.build(&binary, &[0; 32]).unwrap();
+
+            let parsed = parse_loader(&loader).unwrap();
+            assert_eq!(parsed.data_section, data_section);
+            let sizes_start = parsed.instructions.len() + BLOB_ID_SIZE as usize;
+            assert_eq!(
+                loader[sizes_start..sizes_start + WORD_SIZE],
+                large.to_be_bytes()
+            );
+        }
+
+        // The sizes are read at run time, so a blob of exactly that size loads.
+        let blob = [code, &vec![0; large as usize - code.len()]].concat();
+        let loader = LoaderBuilder::default()
+            .with_expected_code_size(large)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+        let vm = Vm::run_prologue(&loader, &blob).unwrap();
+        assert_eq!(vm.revert_code, None);
+        assert_eq!(&vm.memory[vm.copies[1].clone()], data_section);
+    }
+
+    #[test]
+    fn truncated_binaries_are_rejected() {
+        let binary = fixture_binary();
+
+        for len in 0..16 {
+            let truncated = &binary[..len];
+
+            assert!(matches!(
+                extract_data_offset(truncated),
+                Err(Error::BinaryTooShort { len: reported }) if reported == len
+            ));
+            assert!(matches!(
+                LoaderBuilder::default().build(truncated, &[0; 32]),
+                Err(Error::BinaryTooShort { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn data_offset_past_the_end_is_rejected() {
+        let binary = fixture_binary();
+        let offset = extract_data_offset(&binary).unwrap();
+
+        let err = LoaderBuilder::default()
+            .build(&binary[..offset - 1], &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::InvalidDataOffset { len, .. } if len == offset - 1));
+    }
+
+    #[test]
+    fn parses_the_loader_regions() {
+        let binary = fixture_binary();
+        let offset = extract_data_offset(&binary).unwrap();
+
+        let loader = LoaderBuilder::default().build(&binary, &[9; 32]).unwrap();
+        let parsed = parse_loader(&loader).unwrap();
+
+        assert_eq!(parsed.blob_id, [9; 32]);
+        assert_eq!(parsed.data_section, &binary[offset..]);
+        assert_eq!(parsed.metadata, None);
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let binary = fixture_binary();
+        let offset = extract_data_offset(&binary).unwrap();
+        let metadata = "commit 705a862, script/src/main.sw";
+
+        let loader = LoaderBuilder::default()
+            .with_metadata(metadata)
+            .build(&binary, &[9; 32])
+            .unwrap();
+        let parsed = parse_loader(&loader).unwrap();
+
+        assert_eq!(parsed.metadata, Some(metadata));
+        assert_eq!(parsed.data_section, &binary[offset..]);
+    }
+
+    #[test]
+    fn runtime_data_section_includes_overrides() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let offset = extract_data_offset(&binary).unwrap();
+        let overrides = [("U32".to_string(), ConfigurableValue::U32(7))];
+
+        let loader = LoaderBuilder::default()
+            .with_abi(abi.clone())
+            .set_configurable("U32", ConfigurableValue::U32(7))
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        let mut expected = binary[offset..].to_vec();
+        apply_configurables(&mut expected, offset, &abi, &overrides).unwrap();
+        assert_eq!(runtime_data_section(&loader).unwrap(), expected);
+    }
+
+    #[test]
+    fn detects_a_loader_in_the_data_section() {
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default().build(&binary, &[0; 32]).unwrap();
+
+        // A pipeline that put a loader where the data section should be.
+        let wrapped = binary_with_data_section(&loader);
+
+        assert!(is_loader(&loader));
+        assert!(!is_loader(&binary));
+
+        let lenient = LoaderBuilder::default().build(&wrapped, &[0; 32]).unwrap();
+        assert!(contains_nested_loader(&lenient).unwrap());
+        assert!(!contains_nested_loader(&loader).unwrap());
+
+        let err = LoaderBuilder::default()
+            .with_strict(true)
+            .build(&wrapped, &[0; 32])
+            .unwrap_err();
+        assert!(matches!(err, Error::NestedLoader));
+    }
+
+    #[test]
+    fn every_target_builds_the_same_loader() {
+        let binary = fixture_binary();
+        let script = LoaderBuilder::default().build(&binary, &[5; 32]).unwrap();
+
+        for target in [LoaderTarget::Predicate, LoaderTarget::Contract] {
+            let loader = LoaderBuilder::default()
+                .with_target(target)
+                .build(&binary, &[5; 32])
+                .unwrap();
+
+            // The target only restricts the options, the jump is the same everywhere. The node
+            // tests spend through a predicate loader and call a contract loader to show it runs.
+            assert_eq!(loader, script);
+        }
+        assert!(is_loader(&script));
+        assert_eq!(find_blob_id(&script).unwrap(), [5; 32]);
+    }
+
+    #[test]
+    fn predicates_cannot_log() {
+        let binary = fixture_binary();
+        let predicate = LoaderBuilder::default().with_target(LoaderTarget::Predicate);
+
+        for (builder, rejected) in [
+            (predicate.clone().with_debug_log(true), "the debug log"),
+            (predicate.clone().with_blob_id_log(true), "the blob id log"),
+            (
+                predicate
+                    .clone()
+                    .with_debug_log_region(DebugLogBase::Code, 8),
+                "debug log regions",
+            ),
+            (predicate.with_gas_markers(true), "gas markers"),
+        ] {
+            let err = builder.build(&binary, &[0; 32]).unwrap_err();
+
+            assert!(matches!(
+                err,
+                Error::UnsupportedByTarget {
+                    target: LoaderTarget::Predicate,
+                    feature,
+                } if feature == rejected
+            ));
+        }
+
+        LoaderBuilder::default()
+            .with_target(LoaderTarget::Contract)
+            .with_debug_log(true)
+            .build(&binary, &[0; 32])
+            .unwrap();
+    }
+
+    // After `lw` reads the data section length, the pointer must be advanced past that word
+    // before the mode 2 `ldc`. Without the advance the copy would start with the length word
+    // and shift the whole data section by 8 bytes.
+    #[test]
+    fn data_section_copy_skips_the_length_word() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let loader = LoaderBuilder::default()
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        let vm = Vm::run_prologue(&loader, code).unwrap();
+
+        let data_start = vm.jump_target as usize + code.len().next_multiple_of(WORD_SIZE);
+        let copied = &vm.memory[data_start..data_start + data_section.len()];
+        assert_eq!(copied[0], data_section[0]);
+        assert_eq!(copied, data_section);
+        assert_ne!(
+            copied[..WORD_SIZE],
+            (data_section.len() as u64).to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn strips_the_debug_log() {
+        let binary = fixture_binary();
+        let blob_id = [7; 32];
+        let debug_loader = LoaderBuilder::default()
+            .with_debug_log(true)
+            .with_metadata("debug build")
+            .build(&binary, &blob_id)
+            .unwrap();
+
+        let stripped = strip_debug_log(&debug_loader).unwrap();
+
+        assert!(!has_debug_log(&stripped).unwrap());
+        assert_eq!(find_blob_id(&stripped).unwrap(), blob_id);
+        assert_eq!(
+            stripped,
+            LoaderBuilder::default()
+                .with_metadata("debug build")
+                .build(&binary, &blob_id)
+                .unwrap()
+        );
+        assert_eq!(strip_debug_log(&stripped).unwrap(), stripped);
+    }
+
+    #[test]
+    fn stripping_an_optimized_loader_keeps_the_absolute_snapshot() {
+        let binary = fixture_binary();
+        let blob_id = [7; 32];
+        let optimized = LoaderBuilder::default().optimize(true);
+
+        let debug_loader = optimized
+            .clone()
+            .with_debug_log(true)
+            .build(&binary, &blob_id)
+            .unwrap();
+        let stripped = strip_debug_log(&debug_loader).unwrap();
+        let without_debug_log = optimized.build(&binary, &blob_id).unwrap();
+
+        // The `sub(start, $sp, $is)` the optimized build snapshots with becomes a `move` and a
+        // `sub` before the jump.
+        assert_ne!(stripped, without_debug_log);
+        assert_eq!(
+            prologue_byte_len(&stripped).unwrap(),
+            prologue_byte_len(&without_debug_log).unwrap() + Instruction::SIZE
+        );
+        assert!(!has_debug_log(&stripped).unwrap());
+        let (stripped, without_debug_log) = (
+            parse_loader(&stripped).unwrap(),
+            parse_loader(&without_debug_log).unwrap(),
+        );
+        assert_eq!(stripped.blob_id, without_debug_log.blob_id);
+        assert_eq!(stripped.data_section, without_debug_log.data_section);
+    }
+
+    #[test]
+    fn only_strips_loaders() {
+        let binary = fixture_binary();
+
+        assert!(matches!(strip_debug_log(&binary), Err(Error::NotALoader)));
+        assert!(matches!(strip_debug_log(&[]), Err(Error::NotALoader)));
+    }
+
+    #[test]
+    fn default_registers_are_safe_for_loaders() {
+        let default_block = DEFAULT_BASE_REGISTER..DEFAULT_BASE_REGISTER + LOADER_REGISTER_COUNT;
+        let last_block =
+            *SAFE_BASE_REGISTERS.end()..*SAFE_BASE_REGISTERS.end() + LOADER_REGISTER_COUNT;
+
+        assert!(SAFE_BASE_REGISTERS.contains(&DEFAULT_BASE_REGISTER));
+        // None of the registers the VM sets up before entering the code, like `$sp` or `$is`.
+        assert!(default_block.start >= RegId::WRITABLE.to_u8());
+        assert!(*SAFE_BASE_REGISTERS.start() >= RegId::WRITABLE.to_u8());
+
+        // Sway's `compiler_constants.rs`, counting down from the last register.
+        let sway_reserved = [
+            ("DATA_SECTION_REGISTER", 0x3F),
+            ("RETURN_ADDRESS_REGISTER", 0x3E),
+            ("SCRATCH_REGISTER", 0x3D),
+            ("LOCALS_BASE", 0x3C),
+            ("RETURN_VALUE_REGISTER", 0x3B),
+            ("ARG_REG0", 0x3A),
+            ("ARG_REG1", 0x39),
+            ("ARG_REG2", 0x38),
+            ("ARG_REG3", 0x37),
+            ("ARG_REG4", 0x36),
+            ("ARG_REG5", 0x35),
+        ];
+        for (name, register) in sway_reserved {
+            assert!(SWAY_RESERVED_REGISTERS.contains(&register), "{name}");
+            assert!(!default_block.contains(&register), "{name}");
+            assert!(!last_block.contains(&register), "{name}");
+        }
+        assert_eq!(last_block.end, *SWAY_RESERVED_REGISTERS.start());
+    }
+
+    #[test]
+    fn builds_with_another_base_register() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+
+        let loader = LoaderBuilder::default()
+            .with_base_register(0x20)
+            .with_debug_log(true)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        assert!(is_loader(&loader));
+        assert!(has_debug_log(&loader).unwrap());
+        assert_eq!(find_blob_id(&loader).unwrap(), SIMULATED_BLOB_ID);
+
+        let vm = Vm::run_prologue(&loader, code).unwrap();
+        let data_start = vm.jump_target as usize + code.len().next_multiple_of(WORD_SIZE);
+        assert_eq!(
+            &vm.memory[data_start..data_start + data_section.len()],
+            data_section
+        );
+
+        let err = LoaderBuilder::default()
+            .with_base_register(*SAFE_BASE_REGISTERS.end() + 1)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsafeBaseRegister(0x2f)));
+    }
+
+    // The loader snapshots `$sp` before the first `ldc`, assuming the blob lands exactly there.
+    // Check the jump base derived from that snapshot against where the code actually went.
+    #[test]
+    fn jump_base_is_where_the_code_was_loaded() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let loader = LoaderBuilder::default()
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        let vm = Vm::run_prologue(&loader, code).unwrap();
+
+        let [loaded_code, _data_section] = vm.copies.as_slice() else {
+            panic!("expected the code and the data section to be copied");
+        };
+        assert_eq!(&vm.memory[loaded_code.clone()], code);
+
+        let registers = Registers::from_base(DEFAULT_BASE_REGISTER);
+        let start_of_loaded_code = RegId::new(registers.start_of_loaded_code);
+        // `sub $is` and `divi 4` turned the snapshot into a `jmp` operand, undo them.
+        let base = vm.reg(RegId::IS) + vm.reg(start_of_loaded_code) * 4;
+        assert_eq!(base as usize, loaded_code.start);
+        assert_eq!(vm.jump_target, base);
+    }
+
+    #[test]
+    fn pads_the_loader_to_the_requested_size() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let unpadded = LoaderBuilder::default()
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+        let size = unpadded.len() + 100;
+
+        let padded = LoaderBuilder::default()
+            .with_pad_to(size)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        assert_eq!(padded.len(), size);
+        assert_eq!(padded[..unpadded.len()], unpadded);
+        let parsed = parse_loader(&padded).unwrap();
+        assert_eq!(parsed.blob_id, SIMULATED_BLOB_ID);
+        assert_eq!(parsed.data_section, data_section);
+        assert_eq!(parsed.metadata, None);
+
+        let vm = Vm::run_prologue(&padded, code).unwrap();
+        let [loaded_code, copied_data] = vm.copies.as_slice() else {
+            panic!("expected the code and the data section to be copied");
+        };
+        assert_eq!(vm.jump_target as usize, loaded_code.start);
+        assert_eq!(&vm.memory[copied_data.clone()], data_section);
+
+        let with_metadata = LoaderBuilder::default()
+            .with_metadata("padded")
+            .with_pad_to(size)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+        assert_eq!(with_metadata.len(), size);
+        assert_eq!(
+            parse_loader(&with_metadata).unwrap().metadata,
+            Some("padded")
+        );
+
+        let err = LoaderBuilder::default()
+            .with_pad_to(unpadded.len() + 4)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap_err();
+        assert!(matches!(err, Error::PaddingTooSmall { .. }));
+    }
+
+    #[test]
+    fn binaries_without_code_are_rejected() {
+        let binary = [[0; 8], 0u64.to_be_bytes(), [1; 8]].concat();
+
+        assert!(matches!(split_binary(&binary), Err(Error::EmptyCode)));
+        assert!(matches!(
+            crate::compute_blob_id(&binary),
+            Err(Error::EmptyCode)
+        ));
+        assert!(matches!(
+            LoaderBuilder::default().build(&binary, &[0; 32]),
+            Err(Error::EmptyCode)
+        ));
+    }
+
+    #[test]
+    fn measures_the_prologue() {
+        let binary = fixture_binary();
+
+        for (builder, extra_instructions) in [
+            (LoaderBuilder::default(), 0),
+            (LoaderBuilder::default().with_debug_log(true), 3),
+            (LoaderBuilder::default().with_gas_markers(true), 6),
+            (LoaderBuilder::default().with_expected_code_size(16), 5),
+        ] {
+            let loader = builder.build(&binary, &[9; 32]).unwrap();
+
+            let len = prologue_byte_len(&loader).unwrap();
+
+            assert_eq!(len, (12 + extra_instructions) * Instruction::SIZE);
+            assert_eq!(loader[len..len + 32], [9; 32]);
+        }
+    }
+
+    #[test]
+    fn reconstructs_the_binary() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let loader = LoaderBuilder::default()
+            .with_metadata("not part of the binary")
+            .build(&binary, &[0; 32])
+            .unwrap();
+
+        assert_eq!(reconstruct_binary(code, &loader).unwrap(), binary);
+    }
+
+    #[test]
+    fn logs_the_requested_regions() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+
+        let loader = LoaderBuilder::default()
+            .with_expected_code_size(code.len() as u64)
+            .with_debug_log_region(DebugLogBase::Code, 64)
+            .with_debug_log_region(DebugLogBase::DataSection, 16)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        let vm = Vm::run_prologue(&loader, code).unwrap();
+
+        assert_eq!(vm.logs, [code[..64].to_vec(), data_section[..16].to_vec()]);
+        assert_eq!(vm.jump_target as usize, vm.copies[0].start);
+    }
+
+    #[test]
+    fn debug_log_regions_must_fit_an_immediate() {
+        let binary = fixture_binary();
+
+        let err = LoaderBuilder::default()
+            .with_debug_log_region(DebugLogBase::Code, 1 << 18)
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::DebugLogRegionTooLarge(len) if len == 1 << 18));
+    }
+
+    #[test]
+    fn copies_the_data_section_in_chunks() {
+        let data_section: Vec<u8> = (0..100).collect();
+        let code = binary_with_data_section(&[]);
+        let binary = binary_with_data_section(&data_section);
+
+        let loader = LoaderBuilder::default()
+            .with_debug_log(true)
+            .with_max_copy_size(16)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        let vm = Vm::run_prologue(&loader, &code).unwrap();
+
+        let (loaded_code, chunks) = vm.copies.split_first().unwrap();
+        assert_eq!(chunks.len(), 7);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 16));
+        assert!(chunks.windows(2).all(|pair| pair[0].end == pair[1].start));
+        assert_eq!(chunks[0].start, loaded_code.end);
+
+        let reassembled = &vm.memory[chunks[0].start..chunks[6].end];
+        assert_eq!(reassembled, data_section);
+        assert_eq!(vm.logs, [binary]);
+    }
+
+    #[test]
+    fn chunked_copy_has_a_fixed_length_prologue() {
+        let code = binary_with_data_section(&[]);
+        let binary = binary_with_data_section;
+        let builder = LoaderBuilder::default().with_max_copy_size(8);
+
+        let small = builder
+            .build(&binary(&[1; 24]), &SIMULATED_BLOB_ID)
+            .unwrap();
+        let data_section: Vec<u8> = (0..40_000).map(|byte| byte as u8).collect();
+        let large = builder
+            .build(&binary(&data_section), &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        assert_eq!(
+            prologue_byte_len(&large).unwrap(),
+            prologue_byte_len(&small).unwrap()
+        );
+        let vm = Vm::run_prologue(&large, &code).unwrap();
+        let (loaded_code, chunks) = vm.copies.split_first().unwrap();
+        assert_eq!(chunks.len(), 40_000 / 8 + 1);
+        let data_start = loaded_code.end.next_multiple_of(WORD_SIZE);
+        assert_eq!(
+            &vm.memory[data_start..data_start + data_section.len()],
+            data_section
+        );
+    }
+
+    #[test]
+    fn prologue_must_fit_the_blob_id_addi() {
+        let binary = fixture_binary();
+
+        let err = (0..1024)
+            .fold(LoaderBuilder::default(), |builder, _| {
+                builder.with_debug_log_region(DebugLogBase::Code, 8)
+            })
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::PrologueTooLong(len) if len > 4095));
+    }
+
+    #[test]
+    fn max_copy_size_must_be_whole_words() {
+        let binary = fixture_binary();
+
+        for size in [0, 12, 1 << 18] {
+            let err = LoaderBuilder::default()
+                .with_max_copy_size(size)
+                .build(&binary, &[0; 32])
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidMaxCopySize(invalid) if invalid == size));
+        }
+    }
+
+    #[test]
+    fn logs_the_blob_id() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+
+        let loader = LoaderBuilder::default()
+            .with_blob_id_log(true)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+
+        let vm = Vm::run_prologue(&loader, code).unwrap();
+
+        assert_eq!(vm.logs, [SIMULATED_BLOB_ID.to_vec()]);
+        assert_eq!(&vm.memory[vm.copies[1].clone()], data_section);
+        assert!(!has_debug_log(&loader).unwrap());
+    }
+
+    #[test]
+    fn rebuilds_a_loader_with_other_configurables() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default()
+            .with_debug_log(true)
+            .build_loader(&binary, &[5; 32])
+            .unwrap();
+
+        let rebuilt = loader
+            .rebuild_with(&abi, &[("U64".to_string(), ConfigurableValue::U64(8))])
+            .unwrap();
+
+        assert_eq!(rebuilt.parsed().blob_id, [5; 32]);
+        assert_eq!(rebuilt.parsed().instructions, loader.parsed().instructions);
+        let values =
+            crate::read_configurables(rebuilt.parsed().data_section, rebuilt.data_offset(), &abi)
+                .unwrap();
+        assert!(values.contains(&("U64".to_string(), ConfigurableValue::U64(8))));
+        assert_eq!(
+            rebuilt.as_bytes(),
+            LoaderBuilder::default()
+                .with_debug_log(true)
+                .with_abi(abi.clone())
+                .set_configurable("U64", ConfigurableValue::U64(8))
+                .build(&binary, &[5; 32])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn cannot_rebuild_a_loader_with_its_data_section_in_a_blob() {
+        let abi = fixture_abi();
+        let binary = fixture_binary();
+        let loader = LoaderBuilder::default()
+            .with_data_section_strategy(DataSectionStrategy::Blob([2; 32]))
+            .build_loader(&binary, &[5; 32])
+            .unwrap();
+
+        let err = loader
+            .rebuild_with(&abi, &[("U64".to_string(), ConfigurableValue::U64(8))])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::DataSectionInBlob(id) if id == [2; 32]));
+    }
+
+    #[test]
+    fn jump_guard_reverts_on_a_bad_entry_offset() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let run_with_entry_offset = |offset: usize| {
+            let loader = LoaderBuilder::default()
+                .with_jump_guard(true)
+                .with_entry_offset(offset as u32)
+                .build(&binary, &SIMULATED_BLOB_ID)
+                .unwrap();
+            Vm::run_prologue(&loader, code).unwrap()
+        };
+
+        let vm = run_with_entry_offset(0);
+        assert_eq!(vm.revert_code, None);
+        assert_eq!(vm.jump_target as usize, vm.copies[0].start);
+
+        let vm = run_with_entry_offset(code.len() - Instruction::SIZE);
+        assert_eq!(vm.revert_code, None);
+        assert_eq!(
+            vm.jump_target as usize,
+            vm.copies[0].end - Instruction::SIZE
+        );
+
+        // Past the code, the jump would land in the data section.
+        let vm = run_with_entry_offset(code.len());
+        assert_eq!(vm.revert_code, Some(JUMP_GUARD_REVERT_CODE.into()));
+    }
+
+    #[test]
+    fn entry_offset_must_be_instruction_aligned() {
+        let binary = fixture_binary();
+
+        let err = LoaderBuilder::default()
+            .with_entry_offset(6)
+            .build(&binary, &[0; 32])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::InvalidEntryOffset(6)));
+    }
+
+    #[test]
+    fn data_section_can_come_from_a_blob() {
+        let binary = fixture_binary();
+        let (_, data_section) = split_binary(&binary).unwrap();
+        let builder = LoaderBuilder::default().with_metadata("data from a blob");
+        let embedded = builder.build(&binary, &[1; 32]).unwrap();
+
+        let builder = builder.with_data_section_strategy(DataSectionStrategy::Blob([2; 32]));
+        let loader = builder.build(&binary, &[1; 32]).unwrap();
+
+        assert_eq!(
+            builder.configured_data_section(&binary).unwrap(),
+            data_section
+        );
+        let parsed = parse_loader(&loader).unwrap();
+        assert_eq!(parsed.blob_id, [1; 32]);
+        assert_eq!(parsed.data_blob_id, Some([2; 32]));
+        assert!(parsed.data_section.is_empty());
+        assert_eq!(parsed.metadata, Some("data from a blob"));
+        assert!(matches!(
+            reconstruct_binary(&binary, &loader),
+            Err(Error::DataSectionInBlob(id)) if id == [2; 32]
+        ));
+        assert_eq!(parse_loader(&embedded).unwrap().data_blob_id, None);
+        assert!(loader.len() < embedded.len());
+    }
+
+    #[test]
+    fn enumerates_blobs_in_load_order() {
+        let binary = fixture_binary();
+        let builder = LoaderBuilder::default();
+
+        let two_blobs = builder
+            .clone()
+            .with_data_section_strategy(DataSectionStrategy::Blob([2; 32]))
+            .build(&binary, &[1; 32])
+            .unwrap();
+        assert_eq!(multi_blob_ids(&two_blobs).unwrap(), [[1; 32], [2; 32]]);
+
+        let embedded = builder.build(&binary, &[1; 32]).unwrap();
+        assert_eq!(
+            multi_blob_ids(&embedded).unwrap(),
+            [find_blob_id(&embedded).unwrap()]
+        );
+        assert!(matches!(multi_blob_ids(&[]), Err(Error::NotALoader)));
+    }
+
+    #[test]
+    fn fixed_blob_size_ignores_padding_in_the_blob() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let padded_blob = [code, &[0xFF; 64]].concat();
+
+        let loader = LoaderBuilder::default()
+            .with_expected_blob_size(code.len() as u64)
+            .with_debug_log(true)
+            .build(&binary, &SIMULATED_BLOB_ID)
+            .unwrap();
+        let vm = Vm::run_prologue(&loader, &padded_blob).unwrap();
+
+        let [loaded_code, copied_data] = vm.copies.as_slice() else {
+            panic!("expected the code and the data section to be copied");
+        };
+        assert_eq!(&vm.memory[loaded_code.clone()], code);
+        assert_eq!(&vm.memory[copied_data.clone()], data_section);
+        assert_eq!(vm.logs, [binary]);
+    }
+
+    #[test]
+    fn optimized_prologue_behaves_the_same_with_fewer_instructions() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+
+        for builder in [
+            LoaderBuilder::default(),
+            LoaderBuilder::default().with_debug_log(true),
+            LoaderBuilder::default().with_jump_guard(true),
+        ] {
+            let plain = builder.build(&binary, &SIMULATED_BLOB_ID).unwrap();
+            let optimized = builder
+                .optimize(true)
+                .build(&binary, &SIMULATED_BLOB_ID)
+                .unwrap();
+
+            assert!(prologue_byte_len(&optimized).unwrap() < prologue_byte_len(&plain).unwrap());
+            assert!(is_loader(&optimized));
+            assert_eq!(
+                parse_loader(&optimized).unwrap().data_section,
+                parse_loader(&plain).unwrap().data_section
+            );
+
+            let plain = Vm::run_prologue(&plain, code).unwrap();
+            let optimized = Vm::run_prologue(&optimized, code).unwrap();
+            let loaded = |vm: &Vm| -> Vec<_> {
+                vm.copies
+                    .iter()
+                    .map(|copy| vm.memory[copy.clone()].to_vec())
+                    .collect()
+            };
+            assert_eq!(loaded(&optimized), loaded(&plain));
+            assert_eq!(optimized.logs, plain.logs);
+            assert_eq!(optimized.revert_code, None);
+            // The shorter loader moves the stack down, but the jump still lands on the code.
+            assert_eq!(optimized.jump_target as usize, optimized.copies[0].start);
+        }
+    }
+}
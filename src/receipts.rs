@@ -0,0 +1,248 @@
+use fuels::tx::{Receipt, ScriptExecutionResult};
+
+use crate::{
+    LoaderBuilder, GAS_MARKER_BEFORE_JUMP, GAS_MARKER_BLOB_LOADED, GAS_MARKER_DATA_SECTION_LOADED,
+};
+
+/// Which `LogData` receipts [`extract_log_data_by`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSelector {
+    /// The `n`th `LogData` receipt, counting from zero.
+    Index(usize),
+    /// The first `LogData` receipt with the given log id, which is carried in `rb`.
+    Id(u64),
+    /// The data of every `LogData` receipt, concatenated in receipt order.
+    Concatenate,
+}
+
+/// Returns the data of the `LogData` receipts picked by `selector`, or `None` if there are none.
+pub fn extract_log_data_by(receipts: &[Receipt], selector: LogSelector) -> Option<Vec<u8>> {
+    let mut logs = receipts.iter().filter_map(|receipt| match receipt {
+        Receipt::LogData { rb, data, .. } => Some((*rb, data.as_deref()?)),
+        _ => None,
+    });
+
+    match selector {
+        LogSelector::Index(index) => logs.nth(index).map(|(_, data)| data.to_vec()),
+        LogSelector::Id(id) => logs
+            .find(|(log_id, _)| *log_id == id)
+            .map(|(_, data)| data.to_vec()),
+        LogSelector::Concatenate => logs.fold(None, |concatenated, (_, data)| {
+            let mut concatenated: Vec<u8> = concatenated.unwrap_or_default();
+            concatenated.extend_from_slice(data);
+            Some(concatenated)
+        }),
+    }
+}
+
+/// A receipt the loader itself emits before jumping into the loaded code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoaderReceipt {
+    /// A `Log` carrying the marker id in `ra`, see [`LoaderBuilder::with_gas_markers`].
+    GasMarker(u32),
+    /// The `LogData` of the blob id, see [`LoaderBuilder::with_blob_id_log`].
+    BlobId,
+    /// The `LogData` of the loaded code and data section, see [`LoaderBuilder::with_debug_log`],
+    /// or of a region, see [`LoaderBuilder::with_debug_log_region`].
+    DebugLog,
+}
+
+/// The receipts a successful script loader run produces: the loader's own receipts in order,
+/// then whatever the loaded code emits, then a successful `ScriptResult`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptPattern {
+    pub loader_receipts: Vec<LoaderReceipt>,
+}
+
+/// The receipts a script loader built by `builder` emits when it runs successfully.
+pub fn expected_loader_receipts(builder: &LoaderBuilder) -> ReceiptPattern {
+    let marker = |id| {
+        builder
+            .gas_markers_enabled()
+            .then_some(LoaderReceipt::GasMarker(id))
+    };
+    let blob_id = builder
+        .blob_id_log_enabled()
+        .then_some(LoaderReceipt::BlobId);
+    let debug_log = builder
+        .debug_log_enabled()
+        .then_some(LoaderReceipt::DebugLog);
+
+    let regions = std::iter::repeat_n(
+        Some(LoaderReceipt::DebugLog),
+        builder.debug_log_region_count(),
+    );
+
+    ReceiptPattern {
+        loader_receipts: [
+            marker(GAS_MARKER_BLOB_LOADED),
+            blob_id,
+            marker(GAS_MARKER_DATA_SECTION_LOADED),
+            debug_log,
+        ]
+        .into_iter()
+        .chain(regions)
+        .chain([marker(GAS_MARKER_BEFORE_JUMP)])
+        .flatten()
+        .collect(),
+    }
+}
+
+/// Whether `receipts` start with the loader receipts in `pattern` and end with a successful
+/// `ScriptResult`.
+pub fn matches_loader_run(receipts: &[Receipt], pattern: &ReceiptPattern) -> bool {
+    let Some((Receipt::ScriptResult { result, .. }, rest)) = receipts.split_last() else {
+        return false;
+    };
+
+    *result == ScriptExecutionResult::Success
+        && rest.len() >= pattern.loader_receipts.len()
+        && pattern
+            .loader_receipts
+            .iter()
+            .zip(rest)
+            .all(|(expected, receipt)| match (expected, receipt) {
+                (LoaderReceipt::GasMarker(id), Receipt::Log { ra, .. }) => *ra == u64::from(*id),
+                (
+                    LoaderReceipt::BlobId | LoaderReceipt::DebugLog,
+                    Receipt::LogData { ra, rb, .. },
+                ) => *ra == 0 && *rb == 0,
+                _ => false,
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use fuels::types::{Bytes32, ContractId};
+
+    use super::*;
+
+    fn log_data(id: u64, data: &[u8]) -> Receipt {
+        Receipt::LogData {
+            id: ContractId::zeroed(),
+            ra: 0,
+            rb: id,
+            ptr: 0,
+            len: data.len() as u64,
+            digest: Bytes32::zeroed(),
+            pc: 0,
+            is: 0,
+            data: Some(data.to_vec().into()),
+        }
+    }
+
+    fn gas_marker(id: u32) -> Receipt {
+        Receipt::Log {
+            id: ContractId::zeroed(),
+            ra: id.into(),
+            rb: 1_000,
+            rc: 0,
+            rd: 0,
+            pc: 0,
+            is: 0,
+        }
+    }
+
+    fn script_result(result: ScriptExecutionResult) -> Receipt {
+        Receipt::ScriptResult {
+            result,
+            gas_used: 0,
+        }
+    }
+
+    fn receipts() -> Vec<Receipt> {
+        vec![
+            log_data(10, &[1, 2]),
+            Receipt::Return {
+                id: ContractId::zeroed(),
+                val: 0,
+                pc: 0,
+                is: 0,
+            },
+            log_data(20, &[3]),
+            log_data(10, &[4, 5]),
+        ]
+    }
+
+    #[test]
+    fn selects_by_index() {
+        let receipts = receipts();
+
+        assert_eq!(
+            extract_log_data_by(&receipts, LogSelector::Index(0)),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            extract_log_data_by(&receipts, LogSelector::Index(2)),
+            Some(vec![4, 5])
+        );
+        assert_eq!(extract_log_data_by(&receipts, LogSelector::Index(3)), None);
+    }
+
+    #[test]
+    fn selects_the_first_log_with_an_id() {
+        let receipts = receipts();
+
+        assert_eq!(
+            extract_log_data_by(&receipts, LogSelector::Id(10)),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            extract_log_data_by(&receipts, LogSelector::Id(20)),
+            Some(vec![3])
+        );
+        assert_eq!(extract_log_data_by(&receipts, LogSelector::Id(30)), None);
+    }
+
+    #[test]
+    fn concatenates_all_logs() {
+        assert_eq!(
+            extract_log_data_by(&receipts(), LogSelector::Concatenate),
+            Some(vec![1, 2, 3, 4, 5])
+        );
+        assert_eq!(extract_log_data_by(&[], LogSelector::Concatenate), None);
+    }
+
+    #[test]
+    fn expected_receipts_follow_the_loader_options() {
+        assert_eq!(
+            expected_loader_receipts(&LoaderBuilder::default()).loader_receipts,
+            []
+        );
+
+        let builder = LoaderBuilder::default()
+            .with_debug_log(true)
+            .with_gas_markers(true);
+        assert_eq!(
+            expected_loader_receipts(&builder).loader_receipts,
+            [
+                LoaderReceipt::GasMarker(GAS_MARKER_BLOB_LOADED),
+                LoaderReceipt::GasMarker(GAS_MARKER_DATA_SECTION_LOADED),
+                LoaderReceipt::DebugLog,
+                LoaderReceipt::GasMarker(GAS_MARKER_BEFORE_JUMP),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_a_loader_run() {
+        let pattern = expected_loader_receipts(&LoaderBuilder::default().with_debug_log(true));
+        let run = |last| vec![log_data(0, &[1]), log_data(10, &[2]), last];
+
+        assert!(matches_loader_run(
+            &run(script_result(ScriptExecutionResult::Success)),
+            &pattern
+        ));
+        assert!(!matches_loader_run(
+            &run(script_result(ScriptExecutionResult::Revert)),
+            &pattern
+        ));
+        assert!(!matches_loader_run(
+            &[
+                gas_marker(GAS_MARKER_BLOB_LOADED),
+                script_result(ScriptExecutionResult::Success)
+            ],
+            &pattern
+        ));
+    }
+}
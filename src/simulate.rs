@@ -0,0 +1,471 @@
+use std::ops::Range;
+
+use fuel_asm::{Instruction, RegId};
+use fuels::core::constants::WORD_SIZE;
+
+use crate::{
+    find_blob_id,
+    loader::{decode_prologue, Registers},
+    Error, Result,
+};
+
+/// What a loader's prologue built by the time it jumped into the loaded code, or reverted.
+///
+/// Addresses are absolute offsets into the simulated memory, where the loader starts at
+/// [`SIMULATED_IS`] and the stack right after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrologueState {
+    /// Where the mode 1 `ldc` put the blob's code, empty if the prologue reverted first.
+    pub code: Range<usize>,
+    /// Where the mode 2 `ldc`s put the data section, empty if the prologue reverted first.
+    pub data_section: Range<usize>,
+    /// Absolute address the final `jmp` lands on, zero if the prologue reverted.
+    pub jump_target: u64,
+    /// Set if the prologue reverted instead of jumping.
+    pub revert_code: Option<u64>,
+    /// The data of each `logd`, in execution order.
+    pub logs: Vec<Vec<u8>>,
+    pub registers: LoaderRegisterValues,
+}
+
+/// The values of the registers a loader owns, at the end of its prologue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoaderRegisterValues {
+    pub address_of_data_after_code: u64,
+    pub start_of_loaded_code: u64,
+    pub general_use: u64,
+    pub gas_marker: u64,
+    pub scratch: u64,
+    pub code_len: u64,
+    pub debug_log_len: u64,
+}
+
+/// Where [`simulate_loader`] places the loader in memory.
+pub const SIMULATED_IS: u64 = 0x100;
+
+/// How much memory the simulated VM has, the same 64 MiB the real one does.
+const MEMORY_SIZE: usize = 64 * 1024 * 1024;
+
+/// How many instructions a simulated prologue may execute, so a loop that never ends fails
+/// instead of hanging. A real loader loops only over its data section chunks.
+const MAX_STEPS: usize = 1 << 22;
+
+/// Runs `loader`'s prologue offline, with `blob` as the content of the blob it references, and
+/// reports what it built.
+///
+/// Only what a loader prologue does is simulated. Any other instruction, arithmetic that would
+/// overflow, an access outside the simulated memory or a blob access that doesn't point at the
+/// loader's blob id is an error, so arbitrary bytes can be simulated without panicking.
+pub fn simulate_loader(loader: &[u8], blob: &[u8]) -> Result<PrologueState> {
+    let registers = Registers::of(&decode_prologue(loader)?)?;
+    let vm = Vm::run_prologue(loader, blob)?;
+
+    let (code, data_section) = match vm.copies.as_slice() {
+        [] => (0..0, 0..0),
+        [code] => (code.clone(), code.end..code.end),
+        [code, data_section] => (code.clone(), data_section.clone()),
+        [code, first, .., last] => (code.clone(), first.start..last.end),
+    };
+    let value = |reg| vm.reg(RegId::new(reg));
+
+    Ok(PrologueState {
+        code,
+        data_section,
+        jump_target: vm.jump_target,
+        revert_code: vm.revert_code,
+        logs: vm.logs.clone(),
+        registers: LoaderRegisterValues {
+            address_of_data_after_code: value(registers.address_of_data_after_code),
+            start_of_loaded_code: value(registers.start_of_loaded_code),
+            general_use: value(registers.general_use),
+            gas_marker: value(registers.gas_marker),
+            scratch: value(registers.scratch),
+            code_len: value(registers.code_len),
+            debug_log_len: value(registers.debug_log_len),
+        },
+    })
+}
+
+/// Just enough of the VM to run a loader prologue offline: the loader is placed at `$is`, the
+/// stack starts right after it and `blob` is the only blob on chain.
+pub(crate) struct Vm {
+    registers: [u64; 64],
+    pub(crate) memory: Vec<u8>,
+    blob_id: [u8; 32],
+    /// Absolute address the final `jmp` lands on.
+    pub(crate) jump_target: u64,
+    /// Where each `ldc` put its bytes, in execution order.
+    pub(crate) copies: Vec<Range<usize>>,
+    /// The data of each `logd`, in execution order.
+    pub(crate) logs: Vec<Vec<u8>>,
+    /// Set if the loader reverted instead of jumping.
+    pub(crate) revert_code: Option<u64>,
+}
+
+impl Vm {
+    /// Runs until the `jmp` into the loaded code or a revert.
+    pub(crate) fn run_prologue(loader: &[u8], blob: &[u8]) -> Result<Self> {
+        let mut vm = Self {
+            registers: [0; 64],
+            memory: [vec![0; SIMULATED_IS as usize], loader.to_vec()].concat(),
+            blob_id: find_blob_id(loader)?,
+            jump_target: 0,
+            copies: vec![],
+            logs: vec![],
+            revert_code: None,
+        };
+        let stack_start = vm.memory.len().next_multiple_of(WORD_SIZE);
+        vm.memory.resize(stack_start, 0);
+        vm.set(RegId::ONE, 1);
+        vm.set(RegId::IS, SIMULATED_IS);
+        vm.set(RegId::PC, SIMULATED_IS);
+        vm.set(RegId::SP, stack_start as u64);
+
+        let instructions = decode_prologue(loader)?;
+        let mut index = 0;
+        let mut steps = 0;
+        while let Some(instruction) = instructions.get(index) {
+            steps += 1;
+            if steps > MAX_STEPS {
+                return Err(fault("the prologue doesn't reach its `jmp`"));
+            }
+            vm.set(RegId::PC, SIMULATED_IS + (index * Instruction::SIZE) as u64);
+            let jump = vm.step(*instruction, blob)?;
+            index = index
+                .checked_add_signed(jump)
+                .and_then(|index| index.checked_add(1))
+                .ok_or_else(|| fault("a jump leaves the prologue"))?;
+            if vm.revert_code.is_some() {
+                break;
+            }
+        }
+
+        Ok(vm)
+    }
+
+    /// Executes `instruction`, returning how many instructions past the next one to continue at:
+    /// positive to skip forward, negative to jump back.
+    fn step(&mut self, instruction: Instruction, blob: &[u8]) -> Result<isize> {
+        match instruction {
+            Instruction::MOVE(op) => {
+                let (dest, src) = op.unpack();
+                self.set(dest, self.reg(src));
+            }
+            Instruction::MOVI(op) => {
+                let (dest, imm) = op.unpack();
+                self.set(dest, imm.to_u32().into());
+            }
+            Instruction::ADDI(op) => {
+                let (dest, src, imm) = op.unpack();
+                let value = self.reg(src).checked_add(imm.to_u16().into());
+                self.set(dest, value.ok_or_else(|| fault("`addi` overflows"))?);
+            }
+            Instruction::DIVI(op) => {
+                let (dest, src, imm) = op.unpack();
+                let value = self.reg(src).checked_div(imm.to_u16().into());
+                self.set(dest, value.ok_or_else(|| fault("`divi` divides by zero"))?);
+            }
+            Instruction::SUBI(op) => {
+                let (dest, src, imm) = op.unpack();
+                let value = self.reg(src).checked_sub(imm.to_u16().into());
+                self.set(dest, value.ok_or_else(|| fault("`subi` underflows"))?);
+            }
+            Instruction::DIV(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                let value = self.reg(lhs).checked_div(self.reg(rhs));
+                self.set(dest, value.ok_or_else(|| fault("`div` divides by zero"))?);
+            }
+            Instruction::MOD(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                let value = self.reg(lhs).checked_rem(self.reg(rhs));
+                self.set(dest, value.ok_or_else(|| fault("`mod` divides by zero"))?);
+            }
+            Instruction::ADD(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                let value = self.reg(lhs).checked_add(self.reg(rhs));
+                self.set(dest, value.ok_or_else(|| fault("`add` overflows"))?);
+            }
+            Instruction::SUB(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                let value = self.reg(lhs).checked_sub(self.reg(rhs));
+                self.set(dest, value.ok_or_else(|| fault("`sub` underflows"))?);
+            }
+            Instruction::EQ(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                self.set(dest, u64::from(self.reg(lhs) == self.reg(rhs)));
+            }
+            Instruction::LT(op) => {
+                let (dest, lhs, rhs) = op.unpack();
+                self.set(dest, u64::from(self.reg(lhs) < self.reg(rhs)));
+            }
+            Instruction::JNZF(op) => {
+                let (cond, offset, imm) = op.unpack();
+                if self.reg(cond) != 0 {
+                    return self.jump_distance(offset, imm.to_u8().into());
+                }
+            }
+            Instruction::JMPF(op) => {
+                let (offset, imm) = op.unpack();
+                return self.jump_distance(offset, imm.to_u32().into());
+            }
+            Instruction::JNZB(op) => {
+                let (cond, offset, imm) = op.unpack();
+                if self.reg(cond) != 0 {
+                    // Back to `$pc - ($rB + imm + 1)`, i.e. past the jump itself as well.
+                    let distance = self.jump_distance(offset, imm.to_u8().into())?;
+                    return distance
+                        .checked_add(2)
+                        .map(|distance| -distance)
+                        .ok_or_else(|| fault("`jnzb` jumps out of range"));
+                }
+            }
+            Instruction::RVRT(op) => self.revert_code = Some(self.reg(op.unpack())),
+            Instruction::LW(op) => {
+                let (dest, addr, imm) = op.unpack();
+                let start = self
+                    .reg(addr)
+                    .checked_add(u64::from(imm.to_u16()) * WORD_SIZE as u64)
+                    .ok_or_else(|| fault("`lw` address overflows"))?;
+                let word = self
+                    .read(start, WORD_SIZE as u64)?
+                    .try_into()
+                    .expect("read a word");
+                self.set(dest, u64::from_be_bytes(word));
+            }
+            Instruction::BSIZ(op) => {
+                let (dest, id_addr) = op.unpack();
+                self.check_blob_id_at(self.reg(id_addr))?;
+                self.set(dest, blob.len() as u64);
+            }
+            Instruction::LDC(op) => {
+                let (src, offset, len, mode) = op.unpack();
+                let offset = self.reg(offset);
+                let len = self.reg(len);
+                let bytes = match mode.to_u8() {
+                    1 => {
+                        self.check_blob_id_at(self.reg(src))?;
+                        range(offset, len)
+                            .and_then(|range| blob.get(range))
+                            .ok_or_else(|| {
+                                fault(format!(
+                                    "`ldc` reads {len} bytes at {offset}, past the end of the blob"
+                                ))
+                            })?
+                            .to_vec()
+                    }
+                    2 => {
+                        let start = self
+                            .reg(src)
+                            .checked_add(offset)
+                            .ok_or_else(|| fault("`ldc` address overflows"))?;
+                        self.read(start, len)?.to_vec()
+                    }
+                    _ => return Err(Error::UnsupportedInstruction(instruction)),
+                };
+                self.push_to_stack(&bytes)?;
+            }
+            Instruction::LOG(_) => {}
+            Instruction::LOGD(op) => {
+                let (_, _, start, len) = op.unpack();
+                let data = self.read(self.reg(start), self.reg(len))?;
+                self.logs.push(data.to_vec());
+            }
+            Instruction::JMP(op) => {
+                self.jump_target = self
+                    .reg(op.unpack())
+                    .checked_mul(4)
+                    .and_then(|offset| self.reg(RegId::IS).checked_add(offset))
+                    .ok_or_else(|| fault("`jmp` target overflows"))?;
+            }
+            _ => return Err(Error::UnsupportedInstruction(instruction)),
+        }
+
+        Ok(0)
+    }
+
+    /// `$offset + imm`, the number of instructions a relative jump passes over.
+    fn jump_distance(&self, offset: RegId, imm: u64) -> Result<isize> {
+        self.reg(offset)
+            .checked_add(imm)
+            .and_then(|distance| isize::try_from(distance).ok())
+            .ok_or_else(|| fault("a jump goes out of range"))
+    }
+
+    pub(crate) fn reg(&self, reg: RegId) -> u64 {
+        self.registers[usize::from(reg.to_u8())]
+    }
+
+    fn set(&mut self, reg: RegId, value: u64) {
+        self.registers[usize::from(reg.to_u8())] = value;
+    }
+
+    fn read(&self, start: u64, len: u64) -> Result<&[u8]> {
+        range(start, len)
+            .and_then(|range| self.memory.get(range))
+            .ok_or_else(|| {
+                fault(format!(
+                    "reads {len} bytes at {start}, outside the simulated memory"
+                ))
+            })
+    }
+
+    fn check_blob_id_at(&self, addr: u64) -> Result<()> {
+        if self.read(addr, self.blob_id.len() as u64)? != self.blob_id {
+            return Err(Error::SimulationFailed(format!(
+                "blob access at {addr} doesn't point at the loader's blob id"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn push_to_stack(&mut self, bytes: &[u8]) -> Result<()> {
+        let sp = self.reg(RegId::SP) as usize;
+        let end = bytes
+            .len()
+            .checked_next_multiple_of(WORD_SIZE)
+            .and_then(|len| sp.checked_add(len))
+            .filter(|end| *end <= MEMORY_SIZE)
+            .ok_or_else(|| fault("stack grows past the simulated memory"))?;
+        self.memory.resize(self.memory.len().max(end), 0);
+        self.memory[sp..sp + bytes.len()].copy_from_slice(bytes);
+        self.set(RegId::SP, end as u64);
+        self.copies.push(sp..sp + bytes.len());
+
+        Ok(())
+    }
+}
+
+/// The `len` bytes starting at `start`, if they are addressable at all.
+fn range(start: u64, len: u64) -> Option<Range<usize>> {
+    let start = usize::try_from(start).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+
+    Some(start..end)
+}
+
+fn fault(message: impl Into<String>) -> Error {
+    Error::SimulationFailed(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use fuel_asm::op;
+
+    use super::*;
+    use crate::{
+        prologue_byte_len, split_binary, test_fixtures::fixture_binary, LoaderBuilder,
+        CODE_SIZE_MISMATCH_REVERT_CODE, DEFAULT_BASE_REGISTER,
+    };
+
+    #[test]
+    fn reports_where_the_prologue_put_everything() {
+        let binary = fixture_binary();
+        let (code, data_section) = split_binary(&binary).unwrap();
+        let loader = LoaderBuilder::default().build(&binary, &[7; 32]).unwrap();
+
+        let state = simulate_loader(&loader, code).unwrap();
+
+        let stack_start = (SIMULATED_IS as usize + loader.len()).next_multiple_of(WORD_SIZE);
+        assert_eq!(state.code, stack_start..stack_start + code.len());
+        let data_start = state.code.end.next_multiple_of(WORD_SIZE);
+        assert_eq!(
+            state.data_section,
+            data_start..data_start + data_section.len()
+        );
+        assert_eq!(state.jump_target, state.code.start as u64);
+        assert_eq!(state.revert_code, None);
+        assert!(state.logs.is_empty());
+
+        let registers = state.registers;
+        // Left pointing at the data section embedded in the loader, the source of the copy.
+        let embedded_data = SIMULATED_IS as usize + prologue_byte_len(&loader).unwrap() + 32 + 8;
+        assert_eq!(registers.address_of_data_after_code, embedded_data as u64);
+        // Left as the `jmp` operand, relative to `$is` in instructions.
+        assert_eq!(
+            SIMULATED_IS + registers.start_of_loaded_code * 4,
+            state.jump_target
+        );
+        assert_eq!(registers.general_use, data_section.len() as u64);
+    }
+
+    #[test]
+    fn reports_a_revert_before_anything_was_loaded() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let loader = LoaderBuilder::default()
+            .with_expected_code_size(code.len() as u64)
+            .build(&binary, &[7; 32])
+            .unwrap();
+
+        let state = simulate_loader(&loader, &code[..code.len() - 8]).unwrap();
+
+        assert_eq!(
+            state.revert_code,
+            Some(CODE_SIZE_MISMATCH_REVERT_CODE.into())
+        );
+        assert!(state.code.is_empty());
+        assert!(state.data_section.is_empty());
+    }
+
+    #[test]
+    fn rejects_instructions_a_prologue_never_uses() {
+        let binary = fixture_binary();
+        let (code, _) = split_binary(&binary).unwrap();
+        let mut loader = LoaderBuilder::default().build(&binary, &[7; 32]).unwrap();
+        let ret: [u8; Instruction::SIZE] = fuel_asm::op::ret(RegId::ONE).into();
+        loader[Instruction::SIZE..2 * Instruction::SIZE].copy_from_slice(&ret);
+
+        let err = simulate_loader(&loader, code).unwrap_err();
+
+        assert!(matches!(err, Error::UnsupportedInstruction(_)));
+    }
+
+    #[test]
+    fn hostile_prologues_fail_instead_of_panicking() {
+        let base = DEFAULT_BASE_REGISTER;
+        let max = base + 1;
+        // Points `base` at the blob id and reads its first word, all ones, into `max`.
+        let hostile = |body: &[Instruction]| -> Vec<u8> {
+            let len = (body.len() + 4) * Instruction::SIZE;
+            [
+                op::move_(base, RegId::PC),
+                op::addi(base, base, len as u16),
+                op::lw(max, base, 0),
+            ]
+            .iter()
+            .chain(body)
+            .chain(&[op::jmp(base)])
+            .flat_map(|instruction| instruction.to_bytes())
+            .chain([0xFF; 32])
+            .collect()
+        };
+
+        for body in [
+            op::add(base + 2, max, max),
+            op::addi(base + 2, max, 1),
+            op::sub(base + 2, RegId::ZERO, RegId::ONE),
+            op::divi(base + 2, base, 0),
+            op::lw(base + 2, max, 0),
+            op::logd(RegId::ZERO, RegId::ZERO, base, max),
+            op::ldc(base, RegId::ZERO, max, 1),
+            op::ldc(max, RegId::ONE, RegId::ONE, 2),
+            op::jnzf(RegId::ONE, max, 0),
+            op::div(base + 2, max, RegId::ZERO),
+            op::mod_(base + 2, max, RegId::ZERO),
+            op::subi(base + 2, RegId::ZERO, 1),
+            op::jmpf(max, 0),
+            op::jnzb(RegId::ONE, max, 0),
+            // Jumps back to the `lw` before it forever.
+            op::jnzb(RegId::ONE, RegId::ZERO, 0),
+            op::jmp(max),
+        ] {
+            let err = simulate_loader(&hostile(&[body]), &[]).unwrap_err();
+
+            assert!(
+                matches!(err, Error::SimulationFailed(_)),
+                "{body:?} gave {err:?}"
+            );
+        }
+    }
+}
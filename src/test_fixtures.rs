@@ -0,0 +1,20 @@
+//! Fixtures shared by the tests of every module.
+
+/// The compiled `script` project.
+const BINARY_PATH: &str = "./script/out/release/script.bin";
+/// The ABI of the compiled `script` project.
+const ABI_PATH: &str = "./script/out/release/script-abi.json";
+
+pub(crate) fn fixture_binary() -> Vec<u8> {
+    std::fs::read(BINARY_PATH).unwrap()
+}
+
+pub(crate) fn fixture_abi() -> String {
+    std::fs::read_to_string(ABI_PATH).unwrap()
+}
+
+/// A binary with an empty code region (apart from the header) and the given data section.
+pub(crate) fn binary_with_data_section(data_section: &[u8]) -> Vec<u8> {
+    let header = [[0; 8], 16u64.to_be_bytes()].concat();
+    [header.as_slice(), data_section].concat()
+}